@@ -0,0 +1,117 @@
+//! 状態の渡し方や実行方法ごとに実行時間を比べます。
+//!
+//! - 式の評価：呼び出すたびに状態を複製して値で渡す `evaluate` と、状態を借用する `evaluate_ref`
+//! - `while X <= 999999 do X := X + 1` の実行：以前のインタプリタを真似た [`execute_by_value`]、
+//!   `execute_mut`、変数を解決したストア、バイトコードの仮想機械
+//!
+//! [`execute_by_value`] は以前のインタプリタそのものではなく、状態を値で受け渡し、
+//! 残りのコマンドを 1 ステップごとに複製するところだけを真似たものです。
+//!
+//! ```text
+//! cargo bench --bench long_loop
+//! ```
+
+use std::time::{Duration, Instant};
+
+use formal_semantics_of_programming_language::{
+    imp::{parse_aexp, parse_com, Com},
+    Evaluate, EvaluateRef, Execute, ExecuteMut, Number, State,
+};
+
+const RUNS: usize = 10;
+
+/// `f` を `RUNS` 回実行し、最短と中央値の時間を出力します。
+fn bench(name: &str, mut f: impl FnMut()) {
+    f();
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    println!(
+        "{:<32} min {:>10.3?}  median {:>10.3?}",
+        name,
+        times[0],
+        times[RUNS / 2]
+    );
+}
+
+/// 以前のインタプリタを真似て、状態を値で受け渡し、残りのコマンドを 1 ステップごとに複製して実行します。
+fn execute_by_value(c: &Com, state: State) -> State {
+    let mut cmd = c.clone();
+    let mut state = state;
+    loop {
+        let (rest, new_state) = match &cmd {
+            Com::Skip => (None, state),
+            Com::Subst(..) => (None, cmd.execute(state).1),
+            Com::Seq(c_0, c_1) => (Some(c_1.as_ref().clone()), execute_by_value(c_0, state)),
+            Com::If(b, c_0, c_1) => {
+                let (t, state) = b.evaluate(state);
+                let c = if bool::from(t) { c_0 } else { c_1 };
+                (Some(c.as_ref().clone()), state)
+            }
+            Com::While(b, c) => {
+                let (t, state) = b.evaluate(state);
+                if bool::from(t) {
+                    let state = execute_by_value(c, state);
+                    (Some(cmd.clone()), state)
+                } else {
+                    (None, state)
+                }
+            }
+        };
+        state = new_state;
+        match rest {
+            Some(rest) => cmd = rest,
+            None => return state,
+        }
+    }
+}
+
+/// 1 回の計測で式を評価する回数
+const EVALUATIONS: usize = 100_000;
+
+fn main() {
+    let a = parse_aexp("(X + Y) * 2 - Z").unwrap();
+    let names = ["X", "Y", "Z", "A", "B", "C", "D", "E"];
+    let defs: Vec<(&str, Number)> = names.iter().map(|x| (*x, 1.into())).collect();
+    let state = State::from(&defs);
+
+    bench("evaluate (clone State)", || {
+        for _ in 0..EVALUATIONS {
+            let (n, _) = a.evaluate(state.clone());
+            std::hint::black_box(n);
+        }
+    });
+    bench("evaluate_ref (&State)", || {
+        for _ in 0..EVALUATIONS {
+            std::hint::black_box(a.evaluate_ref(&state));
+        }
+    });
+
+    let c: Com = parse_com("while X <= 999999 do X := X + 1").unwrap();
+    let init = State::from(&[("X", 0.into())]);
+
+    bench("emulated old interpreter", || {
+        let state = execute_by_value(&c, init.clone());
+        std::hint::black_box(state);
+    });
+    bench("execute_mut (&mut State)", || {
+        let mut state = init.clone();
+        c.execute_mut(&mut state);
+        std::hint::black_box(state);
+    });
+    let resolved = c.resolve();
+    bench("resolve (slot store)", || {
+        let state = resolved.try_execute(init.clone()).unwrap();
+        std::hint::black_box(state);
+    });
+    let program = c.compile();
+    bench("bytecode (stack VM)", || {
+        let state = program.run(init.clone()).unwrap();
+        std::hint::black_box(state);
+    });
+}
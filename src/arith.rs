@@ -0,0 +1,129 @@
+//! 算術演算の方針
+//!
+//! [`Number`] は 32 ビット整数なので、加減乗算の結果が表せないことがあります。
+//! そのときどうするかを型で選びます。
+//!
+//! | 方針           | 値の型      | オーバーフローしたとき            |
+//! |----------------|-------------|-----------------------------------|
+//! | [`Checked`]    | [`Number`]  | エラー                            |
+//! | [`Wrapping`]   | [`Number`]  | 2^32 を法として折り返す           |
+//! | [`Saturating`] | [`Number`]  | 最大値または最小値に飽和させる    |
+//! | [`Unbounded`]  | [`Integer`] | 起きない（教科書どおりの ℤ）      |
+
+use std::fmt;
+
+use crate::{Integer, Number};
+
+/// 算術演算の方針
+pub trait Arithmetic {
+    /// 整数の値の型
+    type Value: Clone + fmt::Debug + PartialEq + PartialOrd;
+
+    /// 整数リテラル `n` の値を返します。
+    fn number(n: Number) -> Self::Value;
+
+    /// 加算します。結果が表せなければ `None` を返します。
+    fn add(left: Self::Value, right: Self::Value) -> Option<Self::Value>;
+
+    /// 減算します。結果が表せなければ `None` を返します。
+    fn sub(left: Self::Value, right: Self::Value) -> Option<Self::Value>;
+
+    /// 乗算します。結果が表せなければ `None` を返します。
+    fn mul(left: Self::Value, right: Self::Value) -> Option<Self::Value>;
+}
+
+/// オーバーフローをエラーとする方針（既定）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checked;
+
+impl Arithmetic for Checked {
+    type Value = Number;
+
+    fn number(n: Number) -> Number {
+        n
+    }
+
+    fn add(left: Number, right: Number) -> Option<Number> {
+        left.checked_add(right)
+    }
+
+    fn sub(left: Number, right: Number) -> Option<Number> {
+        left.checked_sub(right)
+    }
+
+    fn mul(left: Number, right: Number) -> Option<Number> {
+        left.checked_mul(right)
+    }
+}
+
+/// オーバーフローしたら折り返す方針
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wrapping;
+
+impl Arithmetic for Wrapping {
+    type Value = Number;
+
+    fn number(n: Number) -> Number {
+        n
+    }
+
+    fn add(left: Number, right: Number) -> Option<Number> {
+        Some(left.wrapping_add(right))
+    }
+
+    fn sub(left: Number, right: Number) -> Option<Number> {
+        Some(left.wrapping_sub(right))
+    }
+
+    fn mul(left: Number, right: Number) -> Option<Number> {
+        Some(left.wrapping_mul(right))
+    }
+}
+
+/// オーバーフローしたら飽和させる方針
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Saturating;
+
+impl Arithmetic for Saturating {
+    type Value = Number;
+
+    fn number(n: Number) -> Number {
+        n
+    }
+
+    fn add(left: Number, right: Number) -> Option<Number> {
+        Some(left.saturating_add(right))
+    }
+
+    fn sub(left: Number, right: Number) -> Option<Number> {
+        Some(left.saturating_sub(right))
+    }
+
+    fn mul(left: Number, right: Number) -> Option<Number> {
+        Some(left.saturating_mul(right))
+    }
+}
+
+/// 多倍長整数で計算する方針
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unbounded;
+
+impl Arithmetic for Unbounded {
+    type Value = Integer;
+
+    fn number(n: Number) -> Integer {
+        n.into()
+    }
+
+    fn add(left: Integer, right: Integer) -> Option<Integer> {
+        Some(left + right)
+    }
+
+    fn sub(left: Integer, right: Integer) -> Option<Integer> {
+        Some(left - right)
+    }
+
+    fn mul(left: Integer, right: Integer) -> Option<Integer> {
+        Some(left * right)
+    }
+}
@@ -0,0 +1,728 @@
+//! Hoare 論理の表明言語
+//!
+//! ```text
+//! Aexpv ::= n | X | i | Aexpv + Aexpv | Aexpv - Aexpv | Aexpv * Aexpv
+//! Assn  ::= true | false | Aexpv = Aexpv | Aexpv <= Aexpv
+//!         | ¬Assn | Assn ∧ Assn | Assn ∨ Assn | Assn ⇒ Assn | ∀i. Assn | ∃i. Assn
+//! ```
+//!
+//! `i` は整数変数で、プログラムの変数（ロケーション）`X` とは別の名前空間を持ちます。
+//! 整数変数の値は解釈 [`Interpretation`] が与えます。
+//!
+//! 量化子の範囲は本来 ℤ 全体ですが、充足関係 `σ ⊨ᴵ A` を計算できるように、
+//! 解釈が持つ有限の範囲だけを調べます。
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Write},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    imp::{
+        printer::{write_aexp, ArithForm, ArithOp, ArithSyntax},
+        Aexp, Bexp, BexpImpl, ImpError,
+    },
+    Number, State, Truth, TryEvaluateRef, VarName,
+};
+
+/// 整数変数 `i`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IntVar(String);
+
+impl From<&str> for IntVar {
+    fn from(name: &str) -> Self {
+        IntVar(name.to_string())
+    }
+}
+
+impl From<String> for IntVar {
+    fn from(name: String) -> Self {
+        IntVar(name)
+    }
+}
+
+impl fmt::Display for IntVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl IntVar {
+    /// `avoid` のどれとも異なるように `'` を付けた名前を返します。
+    fn fresh(&self, avoid: &BTreeSet<IntVar>) -> IntVar {
+        let mut i = self.clone();
+        while avoid.contains(&i) {
+            i.0.push('\'');
+        }
+        i
+    }
+}
+
+/// 整数変数を含む算術式
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aexpv {
+    /// 整数 n
+    N(Number),
+    /// プログラム変数 `X`
+    Loc(VarName),
+    /// 整数変数 `i`
+    Var(IntVar),
+    /// 加算 `a_0 + a_1`
+    Add(Box<Aexpv>, Box<Aexpv>),
+    /// 減算 `a_0 - a_1`
+    Sub(Box<Aexpv>, Box<Aexpv>),
+    /// 乗算 `a_0 * a_1`
+    Mul(Box<Aexpv>, Box<Aexpv>),
+}
+
+impl From<Aexp> for Aexpv {
+    fn from(a: Aexp) -> Self {
+        match a {
+            Aexp::N(n) => Aexpv::N(n),
+            Aexp::Loc(x) => Aexpv::Loc(x),
+            Aexp::Add(a0, a1) => Aexpv::Add(Box::new((*a0).into()), Box::new((*a1).into())),
+            Aexp::Sub(a0, a1) => Aexpv::Sub(Box::new((*a0).into()), Box::new((*a1).into())),
+            Aexp::Mul(a0, a1) => Aexpv::Mul(Box::new((*a0).into()), Box::new((*a1).into())),
+        }
+    }
+}
+
+impl From<IntVar> for Aexpv {
+    fn from(i: IntVar) -> Self {
+        Aexpv::Var(i)
+    }
+}
+
+impl Aexpv {
+    /// 整数変数 `i`
+    pub fn var(name: &str) -> Aexpv {
+        Aexpv::Var(name.into())
+    }
+
+    /// 部分式を `f` で置き換えた式を返します。`f` が `None` を返した葉はそのまま残します。
+    fn map_leaves(&self, f: &impl Fn(&Aexpv) -> Option<Aexpv>) -> Aexpv {
+        match self {
+            Aexpv::Add(a0, a1) => {
+                Aexpv::Add(Box::new(a0.map_leaves(f)), Box::new(a1.map_leaves(f)))
+            }
+            Aexpv::Sub(a0, a1) => {
+                Aexpv::Sub(Box::new(a0.map_leaves(f)), Box::new(a1.map_leaves(f)))
+            }
+            Aexpv::Mul(a0, a1) => {
+                Aexpv::Mul(Box::new(a0.map_leaves(f)), Box::new(a1.map_leaves(f)))
+            }
+            leaf => f(leaf).unwrap_or_else(|| leaf.clone()),
+        }
+    }
+
+    /// プログラム変数 `X` を `a` で置き換えた `self[a/X]` を返します。
+    pub fn subst(&self, x: &VarName, a: &Aexpv) -> Aexpv {
+        self.map_leaves(&|leaf| match leaf {
+            Aexpv::Loc(y) if y == x => Some(a.clone()),
+            _ => None,
+        })
+    }
+
+    /// 整数変数 `i` を `a` で置き換えた `self[a/i]` を返します。
+    pub fn subst_int(&self, i: &IntVar, a: &Aexpv) -> Aexpv {
+        self.map_leaves(&|leaf| match leaf {
+            Aexpv::Var(j) if j == i => Some(a.clone()),
+            _ => None,
+        })
+    }
+
+    fn collect(&self, vars: &mut BTreeSet<IntVar>, locs: &mut BTreeSet<VarName>) {
+        match self {
+            Aexpv::N(_) => {}
+            Aexpv::Loc(x) => {
+                locs.insert(x.clone());
+            }
+            Aexpv::Var(i) => {
+                vars.insert(i.clone());
+            }
+            Aexpv::Add(a0, a1) | Aexpv::Sub(a0, a1) | Aexpv::Mul(a0, a1) => {
+                a0.collect(vars, locs);
+                a1.collect(vars, locs);
+            }
+        }
+    }
+
+    /// 現れる整数変数の集合を返します。
+    pub fn free_vars(&self) -> BTreeSet<IntVar> {
+        let mut vars = BTreeSet::new();
+        self.collect(&mut vars, &mut BTreeSet::new());
+        vars
+    }
+
+    /// 現れるプログラム変数の集合を返します。
+    pub fn locations(&self) -> BTreeSet<VarName> {
+        let mut locs = BTreeSet::new();
+        self.collect(&mut BTreeSet::new(), &mut locs);
+        locs
+    }
+
+    /// 整数変数を解釈 `interp` での値に置き換えた算術式を返します。
+    pub fn instantiate(&self, interp: &Interpretation) -> Result<Aexp, AssnError> {
+        let binary = |a0: &Aexpv, a1: &Aexpv| -> Result<_, AssnError> {
+            Ok((
+                Box::new(a0.instantiate(interp)?),
+                Box::new(a1.instantiate(interp)?),
+            ))
+        };
+        Ok(match self {
+            Aexpv::N(n) => Aexp::N(*n),
+            Aexpv::Loc(x) => Aexp::Loc(x.clone()),
+            Aexpv::Var(i) => Aexp::N(interp.get(i).ok_or(AssnError::UnboundIntVar(i.clone()))?),
+            Aexpv::Add(a0, a1) => {
+                let (a0, a1) = binary(a0, a1)?;
+                Aexp::Add(a0, a1)
+            }
+            Aexpv::Sub(a0, a1) => {
+                let (a0, a1) = binary(a0, a1)?;
+                Aexp::Sub(a0, a1)
+            }
+            Aexpv::Mul(a0, a1) => {
+                let (a0, a1) = binary(a0, a1)?;
+                Aexp::Mul(a0, a1)
+            }
+        })
+    }
+
+    /// 状態 `state` と解釈 `interp` のもとでの値を返します。
+    pub fn evaluate(&self, state: &State, interp: &Interpretation) -> Result<Number, AssnError> {
+        Ok(self.instantiate(interp)?.try_evaluate_ref(state)?)
+    }
+}
+
+/// 表明を評価するときのエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssnError {
+    /// 整数変数を置き換えた算術式の評価に失敗した
+    Evaluation(ImpError),
+    /// 整数変数 `i` に解釈が値を与えていない
+    UnboundIntVar(IntVar),
+}
+
+impl From<ImpError> for AssnError {
+    fn from(e: ImpError) -> Self {
+        AssnError::Evaluation(e)
+    }
+}
+
+impl fmt::Display for AssnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssnError::Evaluation(e) => write!(f, "{}", e),
+            AssnError::UnboundIntVar(i) => write!(f, "integer variable {} is unbound", i),
+        }
+    }
+}
+
+impl std::error::Error for AssnError {}
+
+/// 表明
+#[derive(Debug, Clone, PartialEq)]
+pub enum Assn {
+    /// 真偽値 `true`, `false`
+    T(Truth),
+    /// 等値比較 `a_0 = a_1`
+    Eq(Aexpv, Aexpv),
+    /// より小さいか等しい `a_0 <= a_1`
+    Le(Aexpv, Aexpv),
+    /// 否定 `¬A`
+    Not(Box<Assn>),
+    /// 論理積 `A_0 ∧ A_1`
+    And(Box<Assn>, Box<Assn>),
+    /// 論理和 `A_0 ∨ A_1`
+    Or(Box<Assn>, Box<Assn>),
+    /// 含意 `A_0 ⇒ A_1`
+    Implies(Box<Assn>, Box<Assn>),
+    /// 全称量化 `∀i. A`
+    Forall(IntVar, Box<Assn>),
+    /// 存在量化 `∃i. A`
+    Exists(IntVar, Box<Assn>),
+}
+
+impl From<Bexp> for Assn {
+    fn from(b: Bexp) -> Self {
+        b.bexp.into()
+    }
+}
+
+impl From<BexpImpl> for Assn {
+    fn from(b: BexpImpl) -> Self {
+        match b {
+            BexpImpl::T(t) => Assn::T(t),
+            BexpImpl::Eq(a0, a1) => Assn::Eq(a0.into(), a1.into()),
+            BexpImpl::Le(a0, a1) => Assn::Le(a0.into(), a1.into()),
+            BexpImpl::Not(b) => Assn::not((*b).into()),
+            BexpImpl::And(b0, b1) => Assn::and((*b0).into(), (*b1).into()),
+            BexpImpl::Or(b0, b1) => Assn::or((*b0).into(), (*b1).into()),
+            BexpImpl::Dummy => panic!(), // 短絡評価のテスト用
+        }
+    }
+}
+
+impl Assn {
+    /// 真偽値 `true`, `false`
+    pub fn truth(b: bool) -> Assn {
+        Assn::T(b.into())
+    }
+
+    /// 否定 `¬A`
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(a: Assn) -> Assn {
+        Assn::Not(Box::new(a))
+    }
+
+    /// 論理積 `A_0 ∧ A_1`
+    pub fn and(left: Assn, right: Assn) -> Assn {
+        Assn::And(Box::new(left), Box::new(right))
+    }
+
+    /// 論理和 `A_0 ∨ A_1`
+    pub fn or(left: Assn, right: Assn) -> Assn {
+        Assn::Or(Box::new(left), Box::new(right))
+    }
+
+    /// 含意 `A_0 ⇒ A_1`
+    pub fn implies(left: Assn, right: Assn) -> Assn {
+        Assn::Implies(Box::new(left), Box::new(right))
+    }
+
+    /// 全称量化 `∀i. A`
+    pub fn forall(i: &str, a: Assn) -> Assn {
+        Assn::Forall(i.into(), Box::new(a))
+    }
+
+    /// 存在量化 `∃i. A`
+    pub fn exists(i: &str, a: Assn) -> Assn {
+        Assn::Exists(i.into(), Box::new(a))
+    }
+
+    /// 各算術式を `f` で写した表明を返します。量化子の束縛は考えません。
+    fn map(&self, f: &impl Fn(&Aexpv) -> Aexpv) -> Assn {
+        match self {
+            Assn::T(t) => Assn::T(*t),
+            Assn::Eq(a0, a1) => Assn::Eq(f(a0), f(a1)),
+            Assn::Le(a0, a1) => Assn::Le(f(a0), f(a1)),
+            Assn::Not(a) => Assn::not(a.map(f)),
+            Assn::And(a0, a1) => Assn::and(a0.map(f), a1.map(f)),
+            Assn::Or(a0, a1) => Assn::or(a0.map(f), a1.map(f)),
+            Assn::Implies(a0, a1) => Assn::implies(a0.map(f), a1.map(f)),
+            Assn::Forall(i, a) => Assn::Forall(i.clone(), Box::new(a.map(f))),
+            Assn::Exists(i, a) => Assn::Exists(i.clone(), Box::new(a.map(f))),
+        }
+    }
+
+    /// プログラム変数 `X` を `a` で置き換えた `A[a/X]` を返します。
+    ///
+    /// `a` は整数変数を含まないので、量化子に捕獲されることはありません。
+    pub fn subst(&self, x: &VarName, a: &Aexp) -> Assn {
+        let a = Aexpv::from(a.clone());
+        self.map(&|e| e.subst(x, &a))
+    }
+
+    /// 自由な整数変数 `i` を `a` で置き換えた `A[a/i]` を返します。
+    ///
+    /// `a` の整数変数が量化子に捕獲されないよう、必要なら束縛変数の名前を付け替えます。
+    pub fn subst_int(&self, i: &IntVar, a: &Aexpv) -> Assn {
+        let quantified = |j: &IntVar, body: &Assn| -> (IntVar, Assn) {
+            if j == i {
+                return (j.clone(), body.clone());
+            }
+            let fv = a.free_vars();
+            if !fv.contains(j) {
+                return (j.clone(), body.subst_int(i, a));
+            }
+            // 置き換える i と同じ名前にすると、続く置換で a に戻ってしまう
+            let mut avoid = fv;
+            avoid.extend(body.free_vars());
+            avoid.insert(i.clone());
+            let k = j.fresh(&avoid);
+            let body = body.subst_int(j, &Aexpv::Var(k.clone()));
+            (k, body.subst_int(i, a))
+        };
+        match self {
+            Assn::Forall(j, body) => {
+                let (j, body) = quantified(j, body);
+                Assn::Forall(j, Box::new(body))
+            }
+            Assn::Exists(j, body) => {
+                let (j, body) = quantified(j, body);
+                Assn::Exists(j, Box::new(body))
+            }
+            Assn::Not(b) => Assn::not(b.subst_int(i, a)),
+            Assn::And(b0, b1) => Assn::and(b0.subst_int(i, a), b1.subst_int(i, a)),
+            Assn::Or(b0, b1) => Assn::or(b0.subst_int(i, a), b1.subst_int(i, a)),
+            Assn::Implies(b0, b1) => Assn::implies(b0.subst_int(i, a), b1.subst_int(i, a)),
+            atom => atom.map(&|e| e.subst_int(i, a)),
+        }
+    }
+
+    fn collect(&self, vars: &mut BTreeSet<IntVar>, locs: &mut BTreeSet<VarName>) {
+        match self {
+            Assn::T(_) => {}
+            Assn::Eq(a0, a1) | Assn::Le(a0, a1) => {
+                a0.collect(vars, locs);
+                a1.collect(vars, locs);
+            }
+            Assn::Not(a) => a.collect(vars, locs),
+            Assn::And(a0, a1) | Assn::Or(a0, a1) | Assn::Implies(a0, a1) => {
+                a0.collect(vars, locs);
+                a1.collect(vars, locs);
+            }
+            Assn::Forall(i, a) | Assn::Exists(i, a) => {
+                let mut inner = BTreeSet::new();
+                a.collect(&mut inner, locs);
+                inner.remove(i);
+                vars.extend(inner);
+            }
+        }
+    }
+
+    /// 自由な整数変数の集合 `FV(A)` を返します。
+    pub fn free_vars(&self) -> BTreeSet<IntVar> {
+        let mut vars = BTreeSet::new();
+        self.collect(&mut vars, &mut BTreeSet::new());
+        vars
+    }
+
+    /// 現れるプログラム変数の集合を返します。
+    pub fn locations(&self) -> BTreeSet<VarName> {
+        let mut locs = BTreeSet::new();
+        self.collect(&mut BTreeSet::new(), &mut locs);
+        locs
+    }
+
+    /// 充足関係 `σ ⊨ᴵ A` が成り立つかどうかを返します。
+    ///
+    /// 量化子は `interp` の範囲 [`Interpretation::domain`] の整数だけを調べます。
+    pub fn holds(&self, state: &State, interp: &Interpretation) -> Result<bool, AssnError> {
+        Ok(match self {
+            Assn::T(t) => (*t).into(),
+            Assn::Eq(a0, a1) => a0.evaluate(state, interp)? == a1.evaluate(state, interp)?,
+            Assn::Le(a0, a1) => a0.evaluate(state, interp)? <= a1.evaluate(state, interp)?,
+            Assn::Not(a) => !a.holds(state, interp)?,
+            Assn::And(a0, a1) => a0.holds(state, interp)? && a1.holds(state, interp)?,
+            Assn::Or(a0, a1) => a0.holds(state, interp)? || a1.holds(state, interp)?,
+            Assn::Implies(a0, a1) => !a0.holds(state, interp)? || a1.holds(state, interp)?,
+            Assn::Forall(i, a) => {
+                for n in interp.domain() {
+                    if !a.holds(state, &interp.clone().with(i, n.into()))? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            Assn::Exists(i, a) => {
+                for n in interp.domain() {
+                    if a.holds(state, &interp.clone().with(i, n.into()))? {
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+        })
+    }
+}
+
+/// 整数変数の解釈 `I`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interpretation {
+    values: HashMap<IntVar, Number>,
+    domain: RangeInclusive<i32>,
+}
+
+impl Default for Interpretation {
+    /// どの整数変数にも値を与えず、量化子の範囲を `-100..=100` とします。
+    fn default() -> Self {
+        Interpretation::new(-100..=100)
+    }
+}
+
+impl Interpretation {
+    /// どの整数変数にも値を与えず、量化子の範囲を `domain` とする解釈を生成します。
+    pub fn new(domain: RangeInclusive<i32>) -> Interpretation {
+        Interpretation {
+            values: HashMap::new(),
+            domain,
+        }
+    }
+
+    /// 整数変数 `i` の値を `n` に置き換えた解釈 `I[n/i]` を返します。
+    pub fn with(mut self, i: &IntVar, n: Number) -> Interpretation {
+        self.values.insert(i.clone(), n);
+        self
+    }
+
+    /// 整数変数 `i` の値を返します。
+    pub fn get(&self, i: &IntVar) -> Option<Number> {
+        self.values.get(i).copied()
+    }
+
+    /// 量化子が動く範囲を返します。
+    pub fn domain(&self) -> RangeInclusive<i32> {
+        self.domain.clone()
+    }
+}
+
+/// 値を与えた整数変数を名前順に `{i ↦ 1, j ↦ 2}` の形で出力します。
+impl fmt::Display for Interpretation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut values: Vec<_> = self.values.iter().collect();
+        values.sort_by(|a, b| a.0.cmp(b.0));
+
+        write!(f, "{{")?;
+        for (k, (i, n)) in values.iter().enumerate() {
+            if k > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} ↦ {}", i, n)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl ArithSyntax for Aexpv {
+    fn form(&self) -> ArithForm<'_, Self> {
+        match self {
+            Aexpv::N(n) => ArithForm::Atom(n),
+            Aexpv::Loc(x) => ArithForm::Atom(x),
+            Aexpv::Var(i) => ArithForm::Atom(i),
+            Aexpv::Add(left, right) => ArithForm::Binary(ArithOp::Add, left, right),
+            Aexpv::Sub(left, right) => ArithForm::Binary(ArithOp::Sub, left, right),
+            Aexpv::Mul(left, right) => ArithForm::Binary(ArithOp::Mul, left, right),
+        }
+    }
+}
+
+impl fmt::Display for Aexpv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_aexp(f, self, 0)
+    }
+}
+
+/// 表明の結合の強さ
+fn assn_precedence(a: &Assn) -> u8 {
+    match a {
+        Assn::Forall(..) | Assn::Exists(..) => 0,
+        Assn::Implies(..) => 1,
+        Assn::Or(..) => 2,
+        Assn::And(..) => 3,
+        Assn::Not(_) => 4,
+        Assn::T(_) | Assn::Eq(..) | Assn::Le(..) => 5,
+    }
+}
+
+/// 結合の強さが `min` 未満なら括弧で囲んで出力します。
+///
+/// 量化子の本体はできるだけ右に延びるので、`last`（右に何も続かない位置）なら
+/// 量化子は括弧なしで置けます。
+fn write_assn(f: &mut impl Write, a: &Assn, min: u8, last: bool) -> fmt::Result {
+    let quantifier = matches!(a, Assn::Forall(..) | Assn::Exists(..));
+    let paren = assn_precedence(a) < min && !(quantifier && last);
+    let last = last || paren;
+    if paren {
+        f.write_char('(')?;
+    }
+    match a {
+        Assn::T(t) => write!(f, "{}", t)?,
+        Assn::Eq(left, right) | Assn::Le(left, right) => {
+            write_aexp(f, left, 0)?;
+            f.write_str(if let Assn::Eq(..) = a { " = " } else { " <= " })?;
+            write_aexp(f, right, 0)?;
+        }
+        Assn::Not(a) => {
+            f.write_char('¬')?;
+            write_assn(f, a, 4, last)?;
+        }
+        Assn::And(left, right) | Assn::Or(left, right) => {
+            let (op, prec) = match a {
+                Assn::And(..) => ("∧", 3),
+                _ => ("∨", 2),
+            };
+            write_assn(f, left, prec, false)?;
+            write!(f, " {} ", op)?;
+            write_assn(f, right, prec + 1, last)?;
+        }
+        Assn::Implies(left, right) => {
+            // 右結合
+            write_assn(f, left, 2, false)?;
+            f.write_str(" ⇒ ")?;
+            write_assn(f, right, 1, last)?;
+        }
+        Assn::Forall(i, body) | Assn::Exists(i, body) => {
+            let q = if let Assn::Forall(..) = a {
+                '∀'
+            } else {
+                '∃'
+            };
+            write!(f, "{}{}. ", q, i)?;
+            write_assn(f, body, 0, last)?;
+        }
+    }
+    if paren {
+        f.write_char(')')?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Assn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_assn(f, self, 0, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            assn::{Aexpv, Assn, AssnError, IntVar, Interpretation},
+            parse_aexp, parse_bexp,
+        },
+        State,
+    };
+
+    fn assn(b: &str) -> Assn {
+        parse_bexp(b).unwrap().into()
+    }
+
+    fn var(i: &str) -> Aexpv {
+        Aexpv::var(i)
+    }
+
+    fn loc(x: &str) -> Aexpv {
+        Aexpv::Loc(x.into())
+    }
+
+    fn num(n: i32) -> Aexpv {
+        Aexpv::N(n.into())
+    }
+
+    #[test]
+    fn display() {
+        // ∀i. (0 <= i ∧ i <= X ⇒ ∃j. i * j = Y)
+        let a = Assn::forall(
+            "i",
+            Assn::implies(
+                Assn::and(Assn::Le(num(0), var("i")), Assn::Le(var("i"), loc("X"))),
+                Assn::exists(
+                    "j",
+                    Assn::Eq(Aexpv::Mul(Box::new(var("i")), Box::new(var("j"))), loc("Y")),
+                ),
+            ),
+        );
+        assert_eq!("∀i. 0 <= i ∧ i <= X ⇒ ∃j. i * j = Y", a.to_string());
+
+        let a = Assn::and(
+            Assn::forall("i", assn("true")),
+            Assn::not(assn("X = 1 or Y = 2")),
+        );
+        assert_eq!("(∀i. true) ∧ ¬(X = 1 ∨ Y = 2)", a.to_string());
+
+        let a = Assn::implies(Assn::implies(assn("true"), assn("false")), assn("true"));
+        assert_eq!("(true ⇒ false) ⇒ true", a.to_string());
+
+        let a = Assn::or(Assn::not(Assn::exists("i", assn("true"))), assn("false"));
+        assert_eq!("¬(∃i. true) ∨ false", a.to_string());
+    }
+
+    #[test]
+    fn subst_location() {
+        // (X <= Y ∧ ∀i. X = i)[X + 1/X] ≡ X + 1 <= Y ∧ ∀i. X + 1 = i
+        let a = Assn::and(
+            assn("X <= Y"),
+            Assn::forall("i", Assn::Eq(loc("X"), var("i"))),
+        );
+        let a = a.subst(&"X".into(), &parse_aexp("X + 1").unwrap());
+        assert_eq!("X + 1 <= Y ∧ ∀i. X + 1 = i", a.to_string());
+    }
+
+    #[test]
+    fn subst_int_avoids_capture() {
+        // (∃j. i <= j)[j + 1/i] は j を付け替えて ∃j'. j + 1 <= j'
+        let a = Assn::exists("j", Assn::Le(var("i"), var("j")));
+        let a = a.subst_int(
+            &"i".into(),
+            &Aexpv::Add(Box::new(var("j")), Box::new(num(1))),
+        );
+        assert_eq!("∃j'. j + 1 <= j'", a.to_string());
+
+        // (∃j. j <= 0)[j/j'] の束縛変数は j' 以外に付け替える
+        let a = Assn::exists("j", Assn::Le(var("j"), num(0)));
+        let a = a.subst_int(&"j'".into(), &var("j"));
+        assert_eq!("∃j''. j'' <= 0", a.to_string());
+
+        // 束縛されている i は置き換えない
+        let a = Assn::and(
+            Assn::Eq(var("i"), loc("X")),
+            Assn::forall("i", Assn::Eq(var("i"), var("i"))),
+        );
+        assert_eq!(
+            "3 = X ∧ ∀i. i = i",
+            a.subst_int(&"i".into(), &num(3)).to_string()
+        );
+    }
+
+    #[test]
+    fn free_vars() {
+        let a = Assn::and(
+            Assn::Eq(var("i"), loc("X")),
+            Assn::exists("j", Assn::Le(var("j"), var("k"))),
+        );
+        assert_eq!(
+            vec![IntVar::from("i"), IntVar::from("k")],
+            a.free_vars().into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(1, a.locations().len());
+    }
+
+    #[test]
+    fn holds() {
+        let state = State::from(&[("X", 6.into()), ("Y", 7.into())]);
+        let interp = Interpretation::default().with(&"n".into(), 6.into());
+
+        // Bexp と同じ意味を持つ
+        assert_eq!(
+            Ok(true),
+            assn("X <= Y and not X = Y").holds(&state, &interp)
+        );
+        assert_eq!(
+            Ok(true),
+            Assn::Eq(loc("X"), var("n")).holds(&state, &interp)
+        );
+
+        // X は合成数: ∃i. ∃j. 2 <= i ∧ 2 <= j ∧ i * j = X
+        let composite = |x: &str| {
+            Assn::exists(
+                "i",
+                Assn::exists(
+                    "j",
+                    Assn::and(
+                        Assn::and(Assn::Le(num(2), var("i")), Assn::Le(num(2), var("j"))),
+                        Assn::Eq(Aexpv::Mul(Box::new(var("i")), Box::new(var("j"))), loc(x)),
+                    ),
+                ),
+            )
+        };
+        let interp = Interpretation::new(-10..=10);
+        assert_eq!(Ok(true), composite("X").holds(&state, &interp));
+        assert_eq!(Ok(false), composite("Y").holds(&state, &interp));
+        assert_eq!(
+            Ok(true),
+            Assn::forall(
+                "i",
+                Assn::implies(Assn::Le(loc("Y"), var("i")), assn("X <= Y"))
+            )
+            .holds(&state, &interp)
+        );
+
+        // 解釈が値を与えない整数変数
+        assert_eq!(
+            Err(AssnError::UnboundIntVar("m".into())),
+            Assn::Eq(loc("X"), var("m")).holds(&state, &interp)
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! 有限の範囲での表明の妥当性の検査
+//!
+//! 表明 `A` が妥当（`⊨ A`）であるとは、すべての状態 `σ` と解釈 `I` で `σ ⊨ᴵ A` が成り立つことです。
+//! ここでは `A` に現れるプログラム変数と自由な整数変数の値を、
+//! 有限の範囲（たとえば `-8..=8`）のすべての組み合わせについて調べます。
+//! 量化子もこの範囲を動きます。
+//!
+//! 範囲の外にしか反例がない表明は妥当と判定されるので、これは証明ではなく確認のための道具です。
+
+use std::{fmt, ops::RangeInclusive};
+
+use crate::{
+    imp::assn::{Assn, AssnError, Interpretation},
+    State,
+};
+
+/// [`check`] で調べる範囲の既定値
+pub const DEFAULT_BOX: RangeInclusive<i32> = -8..=8;
+
+/// 表明が成り立たない状態と解釈
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterexample {
+    /// 状態 `σ`
+    pub state: State,
+    /// 自由な整数変数の解釈 `I`
+    pub interp: Interpretation,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "σ = {}, I = {}", self.state, self.interp)
+    }
+}
+
+/// 有限の範囲での妥当性
+#[derive(Debug, Clone, PartialEq)]
+pub enum Validity {
+    /// 範囲内のすべての状態と解釈で成り立つ
+    Valid,
+    /// 成り立たない状態と解釈が見つかった
+    Invalid(Counterexample),
+}
+
+/// 範囲内での検査を終えられなかった理由
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckError {
+    /// 調べる範囲が空
+    EmptyDomain(RangeInclusive<i32>),
+    /// 状態 `σ` と解釈 `I` のもとで表明を評価できなかった
+    Evaluation {
+        error: AssnError,
+        state: State,
+        interp: Interpretation,
+    },
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::EmptyDomain(domain) => {
+                write!(f, "domain {}..={} is empty", domain.start(), domain.end())
+            }
+            CheckError::Evaluation {
+                error,
+                state,
+                interp,
+            } => write!(f, "{} at σ = {}, I = {}", error, state, interp),
+        }
+    }
+}
+
+impl std::error::Error for CheckError {}
+
+/// `a` に現れる変数が `domain` の値をとるすべての場合に `a` が成り立つかを調べます。
+///
+/// 状態は名前順に最初の変数が最も速く変わる順に列挙し、最初に見つかった反例を返します。
+/// 評価できない状態があっても残りの状態を調べ続け、反例が見つからなければ、
+/// 最初に評価できなかった状態と解釈をエラーとして返します。
+pub fn check(a: &Assn, domain: RangeInclusive<i32>) -> Result<Validity, Box<CheckError>> {
+    if domain.is_empty() {
+        return Err(Box::new(CheckError::EmptyDomain(domain)));
+    }
+    let locations: Vec<_> = a.locations().into_iter().collect();
+    let vars: Vec<_> = a.free_vars().into_iter().collect();
+
+    // 各変数の値を domain の中で数え上げる
+    let mut error = None;
+    let mut values = vec![*domain.start(); locations.len() + vars.len()];
+    loop {
+        let mut state = State::init();
+        for (x, n) in locations.iter().zip(&values) {
+            state = state.update_variable(x, (*n).into());
+        }
+        let mut interp = Interpretation::new(domain.clone());
+        for (i, n) in vars.iter().zip(&values[locations.len()..]) {
+            interp = interp.with(i, (*n).into());
+        }
+
+        match a.holds(&state, &interp) {
+            Ok(true) => {}
+            Ok(false) => return Ok(Validity::Invalid(Counterexample { state, interp })),
+            Err(e) => {
+                error.get_or_insert(Box::new(CheckError::Evaluation {
+                    error: e,
+                    state,
+                    interp,
+                }));
+            }
+        }
+
+        let Some(k) = values.iter().position(|n| n < domain.end()) else {
+            return error.map_or(Ok(Validity::Valid), Err);
+        };
+        values[k] += 1;
+        values[..k].fill(*domain.start());
+    }
+}
+
+/// `vcs` のどれもが `domain` の範囲で妥当かを調べます。
+/// 妥当でないものがあれば、最初のものの番号と反例を返します。
+///
+/// 評価できない検証条件があっても残りを調べ続け、反例が見つからなければ最初のエラーを返します。
+pub fn check_all(
+    vcs: &[Assn],
+    domain: RangeInclusive<i32>,
+) -> Result<Option<(usize, Counterexample)>, Box<CheckError>> {
+    let mut error = None;
+    for (k, vc) in vcs.iter().enumerate() {
+        match check(vc, domain.clone()) {
+            Ok(Validity::Valid) => {}
+            Ok(Validity::Invalid(e)) => return Ok(Some((k, e))),
+            Err(e) if matches!(*e, CheckError::EmptyDomain(_)) => return Err(e),
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    error.map_or(Ok(None), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            assn::{Aexpv, Assn, AssnError, Interpretation},
+            bounded::{check, check_all, CheckError, Counterexample, Validity, DEFAULT_BOX},
+            parse_bexp, parse_com,
+            vcgen::{verification_conditions, ACom},
+            ImpError,
+        },
+        State,
+    };
+
+    fn assn(b: &str) -> Assn {
+        parse_bexp(b).unwrap().into()
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(
+            Ok(Validity::Valid),
+            check(&assn("X <= Y or Y <= X"), DEFAULT_BOX)
+        );
+        assert_eq!(
+            Ok(Validity::Valid),
+            check(&assn("not (X = Y) or X - Y = 0"), DEFAULT_BOX)
+        );
+        // ∀i. X <= i ∨ i <= X
+        let a = Assn::forall(
+            "i",
+            Assn::or(
+                Assn::Le(Aexpv::Loc("X".into()), Aexpv::var("i")),
+                Assn::Le(Aexpv::var("i"), Aexpv::Loc("X".into())),
+            ),
+        );
+        assert_eq!(Ok(Validity::Valid), check(&a, -3..=3));
+    }
+
+    #[test]
+    fn counterexample() {
+        // 最初の反例は X = -8, Y = -8
+        assert_eq!(
+            Ok(Validity::Invalid(Counterexample {
+                state: State::from(&[("X", (-8).into()), ("Y", (-8).into())]),
+                interp: Interpretation::new(DEFAULT_BOX),
+            })),
+            check(&assn("X <= Y - 1"), DEFAULT_BOX)
+        );
+
+        // 自由な整数変数の値も探す: X * X = n は X = -2, n = -2 で成り立たない
+        let a = Assn::Eq(
+            Aexpv::Mul(
+                Box::new(Aexpv::Loc("X".into())),
+                Box::new(Aexpv::Loc("X".into())),
+            ),
+            Aexpv::var("n"),
+        );
+        let Ok(Validity::Invalid(e)) = check(&a, -2..=2) else {
+            panic!()
+        };
+        assert_eq!("σ = {X ↦ -2}, I = {n ↦ -2}", e.to_string());
+    }
+
+    #[test]
+    fn check_verification_conditions() {
+        let c = ACom::annotate(
+            &parse_com("while 1 <= X do X := X - 1").unwrap(),
+            vec![assn("0 <= X")],
+        )
+        .unwrap();
+        let vcs = verification_conditions(&assn("0 <= X"), &c, &assn("X = 0"));
+        assert_eq!(Ok(None), check_all(&vcs, DEFAULT_BOX));
+
+        // 不変条件が弱すぎると、ループを抜けた後の検証条件が成り立たない
+        let c = ACom::annotate(
+            &parse_com("while 1 <= X do X := X - 1").unwrap(),
+            vec![assn("true")],
+        )
+        .unwrap();
+        let vcs = verification_conditions(&assn("0 <= X"), &c, &assn("X = 0"));
+        let Ok(Some((2, e))) = check_all(&vcs, DEFAULT_BOX) else {
+            panic!()
+        };
+        assert_eq!(State::from(&[("X", (-8).into())]), e.state);
+    }
+
+    #[test]
+    fn evaluation_error() {
+        let Err(e) = check(&assn("X <= 2147483647 + 1"), DEFAULT_BOX) else {
+            panic!()
+        };
+        let CheckError::Evaluation { error, state, .. } = *e else {
+            panic!("{}", e)
+        };
+        assert_eq!(
+            AssnError::Evaluation(ImpError::Overflow(
+                crate::imp::parse_aexp("2147483647 + 1").unwrap()
+            )),
+            error
+        );
+        assert_eq!(State::from(&[("X", (-8).into())]), state);
+
+        // 評価できない状態があっても、その後の反例を報告する
+        assert_eq!(
+            Ok(Validity::Invalid(Counterexample {
+                state: State::from(&[("X", 1.into())]),
+                interp: Interpretation::new(-1..=1),
+            })),
+            check(&assn("X - 2147483647 - 1 <= 0 and X <= 0"), -1..=1)
+        );
+    }
+
+    #[test]
+    fn empty_domain() {
+        #[allow(clippy::reversed_empty_ranges)]
+        let domain = 1..=0;
+        assert_eq!(
+            Err(Box::new(CheckError::EmptyDomain(domain.clone()))),
+            check(&assn("false"), domain.clone())
+        );
+        assert_eq!(
+            Err(Box::new(CheckError::EmptyDomain(domain.clone()))),
+            check_all(&[assn("X = 0")], domain)
+        );
+    }
+}
@@ -0,0 +1,662 @@
+//! スタックマシンのバイトコードへのコンパイル
+//!
+//! コマンドを整数のスタックを使う命令列にコンパイルし、仮想機械で実行します。
+//! 変数はコンパイル時に [`VarTable`] でスロットの番号に解決するので、実行中は [`Store`] を添字で引くだけです。
+//! 真偽値は整数の `1`（真）と `0`（偽）で表します。
+//!
+//! ```text
+//! while X <= 9 do X := X + 1
+//!
+//! 0: load 0     ; X
+//! 1: push 9
+//! 2: le
+//! 3: jz 9
+//! 4: load 0     ; X
+//! 5: push 1
+//! 6: add
+//! 7: store 0    ; X
+//! 8: jmp 0
+//! ```
+//!
+//! `and`/`or` は分岐にコンパイルするので、インタプリタと同じく短絡評価します。
+//! 未定義の変数の読み出しやオーバーフローも、インタプリタと同じエラーとして報告します。
+//! 誤ってコンパイルされた命令列でも止まらずに済むよう、スタックが空のときの `pop` や
+//! 存在しないスロットの参照は、仮想機械のエラー [`VmError`] として報告します。
+
+use std::fmt;
+
+use crate::{
+    imp::{
+        resolve::{Store, VarTable},
+        Aexp, BexpImpl, Com, ImpError,
+    },
+    Number, State,
+};
+
+/// 命令
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    /// 整数をスタックに積む
+    Push(Number),
+    /// スロットの値をスタックに積む
+    Load(usize),
+    /// スタックから降ろした値をスロットに書き込む
+    Store(usize),
+    /// 2 つ降ろして和を積む
+    Add,
+    /// 2 つ降ろして差を積む
+    Sub,
+    /// 2 つ降ろして積を積む
+    Mul,
+    /// 2 つ降ろして等しければ 1、そうでなければ 0 を積む
+    Eq,
+    /// 2 つ降ろして先に積んだ方が小さいか等しければ 1、そうでなければ 0 を積む
+    Le,
+    /// 無条件に飛ぶ
+    Jmp(usize),
+    /// 降ろした値が 0 なら飛ぶ
+    Jz(usize),
+}
+
+/// コンパイルしたプログラム
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// 命令列
+    pub code: Vec<Instr>,
+    /// 各スロットに対応する変数の表
+    pub table: VarTable,
+    /// 算術命令の番地と、その命令を生成した式。オーバーフローを報告するのに使います。
+    sources: Vec<(usize, Aexp)>,
+    /// 代入・`if`・`while` の最初の命令の番地と、そのコマンドの番号
+    entries: Vec<(usize, usize)>,
+    /// `if`・`while` の条件で飛ぶ `jz` の番地と、そのコマンドの番号
+    branches: Vec<(usize, usize)>,
+}
+
+impl Com {
+    /// 自身をバイトコードにコンパイルします。
+    pub fn compile(&self) -> Program {
+        let mut compiler = Compiler::default();
+        compiler.com(self);
+        Program {
+            code: compiler.code,
+            table: compiler.table,
+            sources: compiler.sources,
+            entries: compiler.entries,
+            branches: compiler.branches,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    code: Vec<Instr>,
+    table: VarTable,
+    sources: Vec<(usize, Aexp)>,
+    entries: Vec<(usize, usize)>,
+    branches: Vec<(usize, usize)>,
+}
+
+impl Compiler {
+    fn emit(&mut self, instr: Instr) -> usize {
+        self.code.push(instr);
+        self.code.len() - 1
+    }
+
+    /// 飛び先を `self.code.len()` にします。
+    fn patch(&mut self, at: usize) {
+        let target = self.code.len();
+        match &mut self.code[at] {
+            Instr::Jmp(to) | Instr::Jz(to) => *to = target,
+            _ => unreachable!(),
+        }
+    }
+
+    fn aexp(&mut self, a: &Aexp) {
+        let (a_0, a_1, op) = match a {
+            Aexp::N(n) => {
+                self.emit(Instr::Push(*n));
+                return;
+            }
+            Aexp::Loc(x) => {
+                let slot = self.table.intern(x);
+                self.emit(Instr::Load(slot));
+                return;
+            }
+            Aexp::Add(a_0, a_1) => (a_0, a_1, Instr::Add),
+            Aexp::Sub(a_0, a_1) => (a_0, a_1, Instr::Sub),
+            Aexp::Mul(a_0, a_1) => (a_0, a_1, Instr::Mul),
+        };
+        self.aexp(a_0);
+        self.aexp(a_1);
+        let at = self.emit(op);
+        self.sources.push((at, a.clone()));
+    }
+
+    fn bexp(&mut self, b: &BexpImpl) {
+        match b {
+            BexpImpl::T(t) => {
+                self.emit(Instr::Push(Number(bool::from(*t) as i32)));
+            }
+            BexpImpl::Eq(a_0, a_1) => {
+                self.aexp(a_0);
+                self.aexp(a_1);
+                self.emit(Instr::Eq);
+            }
+            BexpImpl::Le(a_0, a_1) => {
+                self.aexp(a_0);
+                self.aexp(a_1);
+                self.emit(Instr::Le);
+            }
+            BexpImpl::Not(b) => {
+                // b = 0 なら 1
+                self.bexp(b);
+                self.emit(Instr::Push(Number(0)));
+                self.emit(Instr::Eq);
+            }
+            BexpImpl::And(b_0, b_1) => {
+                self.bexp(b_0);
+                let to_false = self.emit(Instr::Jz(0));
+                self.bexp(b_1);
+                let to_end = self.emit(Instr::Jmp(0));
+                self.patch(to_false);
+                self.emit(Instr::Push(Number(0)));
+                self.patch(to_end);
+            }
+            BexpImpl::Or(b_0, b_1) => {
+                self.bexp(b_0);
+                let to_right = self.emit(Instr::Jz(0));
+                self.emit(Instr::Push(Number(1)));
+                let to_end = self.emit(Instr::Jmp(0));
+                self.patch(to_right);
+                self.bexp(b_1);
+                self.patch(to_end);
+            }
+            BexpImpl::Dummy => panic!(), // 短絡評価のテスト用
+        }
+    }
+
+    fn com(&mut self, c: &Com) {
+        // 代入・if・while に前順で番号を振る
+        let id = self.entries.len();
+        if !matches!(c, Com::Skip | Com::Seq(..)) {
+            self.entries.push((self.code.len(), id));
+        }
+        match c {
+            Com::Skip => {}
+            Com::Subst(x, a) => {
+                self.aexp(a);
+                let slot = self.table.intern(x);
+                self.emit(Instr::Store(slot));
+            }
+            Com::Seq(c_0, c_1) => {
+                self.com(c_0);
+                self.com(c_1);
+            }
+            Com::If(b, c_0, c_1) => {
+                self.bexp(&b.bexp);
+                let to_else = self.emit(Instr::Jz(0));
+                self.branches.push((to_else, id));
+                self.com(c_0);
+                let to_end = self.emit(Instr::Jmp(0));
+                self.patch(to_else);
+                self.com(c_1);
+                self.patch(to_end);
+            }
+            Com::While(b, c) => {
+                let head = self.code.len();
+                self.bexp(&b.bexp);
+                let to_end = self.emit(Instr::Jz(0));
+                self.branches.push((to_end, id));
+                self.com(c);
+                self.emit(Instr::Jmp(head));
+                self.patch(to_end);
+            }
+        }
+    }
+}
+
+/// 仮想機械のエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    /// プログラムの実行時エラー
+    Imp(ImpError),
+    /// 空のスタックから降ろそうとした
+    StackUnderflow,
+    /// 存在しないスロットを参照した
+    BadSlot(usize),
+    /// 生成した式を記録していない算術命令がオーバーフローした
+    MissingSource,
+}
+
+impl From<ImpError> for VmError {
+    fn from(e: ImpError) -> Self {
+        VmError::Imp(e)
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::Imp(e) => write!(f, "{}", e),
+            VmError::StackUnderflow => f.write_str("stack underflow"),
+            VmError::BadSlot(slot) => write!(f, "slot {} does not exist", slot),
+            VmError::MissingSource => f.write_str("arithmetic instruction without source"),
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+/// 仮想機械
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vm<'a> {
+    program: &'a Program,
+    /// 次に実行する命令の番地
+    pub pc: usize,
+    /// スタック
+    pub stack: Vec<Number>,
+    /// 各スロットの値
+    pub store: Store,
+}
+
+impl<'a> Vm<'a> {
+    /// 状態 `state` の変数の値をスロットに読み込んで、`program` を先頭から実行する仮想機械を作ります。
+    pub fn new(program: &'a Program, state: &State) -> Vm<'a> {
+        Vm {
+            program,
+            pc: 0,
+            stack: vec![],
+            store: program.table.store(state),
+        }
+    }
+
+    /// 命令列の終わりに達したかどうか
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.code.len()
+    }
+
+    /// 命令を 1 つ実行します。
+    pub fn step(&mut self) -> Result<(), VmError> {
+        let instr = self.program.code[self.pc];
+        self.pc += 1;
+        match instr {
+            Instr::Push(n) => self.stack.push(n),
+            Instr::Load(slot) => {
+                self.check_slot(slot)?;
+                match self.store.get(slot) {
+                    Some(n) => self.stack.push(n),
+                    None => {
+                        let x = self.program.table.name(slot).clone();
+                        return Err(ImpError::UndefinedVariable(x).into());
+                    }
+                }
+            }
+            Instr::Store(slot) => {
+                let n = self.pop()?;
+                self.check_slot(slot)?;
+                self.store.set(slot, n);
+            }
+            Instr::Add | Instr::Sub | Instr::Mul => {
+                let (n, m) = self.pop2()?;
+                let p = match instr {
+                    Instr::Add => n.checked_add(m),
+                    Instr::Sub => n.checked_sub(m),
+                    _ => n.checked_mul(m),
+                };
+                match p {
+                    Some(p) => self.stack.push(p),
+                    None => return Err(ImpError::Overflow(self.source(self.pc - 1)?).into()),
+                }
+            }
+            Instr::Eq => {
+                let (n, m) = self.pop2()?;
+                self.stack.push(Number((n == m) as i32));
+            }
+            Instr::Le => {
+                let (n, m) = self.pop2()?;
+                self.stack.push(Number((n <= m) as i32));
+            }
+            Instr::Jmp(to) => self.pc = to,
+            Instr::Jz(to) => {
+                if self.pop()? == 0 {
+                    self.pc = to;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 命令列の終わりまで実行します。
+    pub fn run(&mut self) -> Result<(), VmError> {
+        while !self.is_halted() {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// スロットの値を `state` に書き戻した状態を返します。
+    pub fn state(&self, state: State) -> State {
+        self.program.table.state(&self.store, state)
+    }
+
+    /// 変数表にないスロットならエラーを返します。
+    fn check_slot(&self, slot: usize) -> Result<(), VmError> {
+        if slot < self.program.table.len() {
+            Ok(())
+        } else {
+            Err(VmError::BadSlot(slot))
+        }
+    }
+
+    fn pop(&mut self) -> Result<Number, VmError> {
+        self.stack.pop().ok_or(VmError::StackUnderflow)
+    }
+
+    /// 後に積んだ方を右にして 2 つ降ろします。
+    fn pop2(&mut self) -> Result<(Number, Number), VmError> {
+        let m = self.pop()?;
+        let n = self.pop()?;
+        Ok((n, m))
+    }
+
+    fn source(&self, at: usize) -> Result<Aexp, VmError> {
+        let sources = &self.program.sources;
+        let k = sources
+            .binary_search_by_key(&at, |(at, _)| *at)
+            .map_err(|_| VmError::MissingSource)?;
+        Ok(sources[k].1.clone())
+    }
+}
+
+impl Program {
+    /// 番地 `pc` から始まる代入・`if`・`while` の番号を返します。
+    ///
+    /// 番号はコマンドの構文木で代入・`if`・`while` を前順に数えたものです。
+    pub(crate) fn entry(&self, pc: usize) -> Option<usize> {
+        lookup(&self.entries, pc)
+    }
+
+    /// 番地 `pc` の命令が `if`・`while` の条件で飛ぶ `jz` なら、そのコマンドの番号を返します。
+    pub(crate) fn branch(&self, pc: usize) -> Option<usize> {
+        lookup(&self.branches, pc)
+    }
+
+    /// 状態 `state` から実行し、実行を終えた状態を返します。
+    pub fn run(&self, state: State) -> Result<State, VmError> {
+        let mut vm = Vm::new(self, &state);
+        vm.run()?;
+        Ok(vm.state(state))
+    }
+
+    /// 状態 `state` から高々 `fuel` 個の命令を実行し、実行した命令と直後のスタックを記録します。
+    pub fn trace(&self, state: State, fuel: u64) -> Trace {
+        let mut vm = Vm::new(self, &state);
+        let mut steps = vec![];
+        let mut result = None;
+        for _ in 0..fuel {
+            if vm.is_halted() {
+                break;
+            }
+            let pc = vm.pc;
+            if let Err(e) = vm.step() {
+                result = Some(Err(e));
+                break;
+            }
+            steps.push(TraceStep {
+                pc,
+                instr: self.code[pc],
+                stack: vm.stack.clone(),
+            });
+        }
+        if result.is_none() && vm.is_halted() {
+            result = Some(Ok(vm.state(state)));
+        }
+        Trace { steps, result }
+    }
+}
+
+fn lookup(table: &[(usize, usize)], pc: usize) -> Option<usize> {
+    table
+        .binary_search_by_key(&pc, |(at, _)| *at)
+        .ok()
+        .map(|k| table[k].1)
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instr::Push(n) => write!(f, "push {}", n),
+            Instr::Load(slot) => write!(f, "load {}", slot),
+            Instr::Store(slot) => write!(f, "store {}", slot),
+            Instr::Add => f.write_str("add"),
+            Instr::Sub => f.write_str("sub"),
+            Instr::Mul => f.write_str("mul"),
+            Instr::Eq => f.write_str("eq"),
+            Instr::Le => f.write_str("le"),
+            Instr::Jmp(to) => write!(f, "jmp {}", to),
+            Instr::Jz(to) => write!(f, "jz {}", to),
+        }
+    }
+}
+
+/// 逆アセンブルした命令列を 1 行に 1 命令ずつ出力します。
+/// `load`/`store` にはスロットの変数名を注釈します。
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.code.len().saturating_sub(1).to_string().len();
+        for (pc, instr) in self.code.iter().enumerate() {
+            if pc > 0 {
+                writeln!(f)?;
+            }
+            match instr {
+                Instr::Load(slot) | Instr::Store(slot) if *slot < self.table.len() => write!(
+                    f,
+                    "{:>width$}: {:<10} ; {}",
+                    pc,
+                    instr.to_string(),
+                    self.table.name(*slot)
+                )?,
+                _ => write!(f, "{:>width$}: {}", pc, instr)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 実行した命令
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    /// 命令の番地
+    pub pc: usize,
+    /// 命令
+    pub instr: Instr,
+    /// 命令を実行した直後のスタック
+    pub stack: Vec<Number>,
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stack: Vec<_> = self.stack.iter().map(Number::to_string).collect();
+        write!(
+            f,
+            "{}: {:<10} [{}]",
+            self.pc,
+            self.instr.to_string(),
+            stack.join(", ")
+        )
+    }
+}
+
+/// [`Program::trace`] の結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    /// 実行した命令。エラーになった命令は含みません。
+    pub steps: Vec<TraceStep>,
+    /// 実行の結果。燃料を使い切ったら `None` です。
+    pub result: Option<Result<State, VmError>>,
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+        match &self.result {
+            Some(Ok(state)) => write!(f, "halted with {}", state),
+            Some(Err(e)) => write!(f, "error: {}", e),
+            None => f.write_str("out of fuel"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            bytecode::{Instr, VmError},
+            parse_aexp, parse_com, ImpError,
+        },
+        Number, State, TryExecute, VarName,
+    };
+
+    #[test]
+    fn compile() {
+        let program = parse_com("while X <= 9 do X := X + 1").unwrap().compile();
+        assert_eq!(
+            vec![
+                Instr::Load(0),
+                Instr::Push(9.into()),
+                Instr::Le,
+                Instr::Jz(9),
+                Instr::Load(0),
+                Instr::Push(1.into()),
+                Instr::Add,
+                Instr::Store(0),
+                Instr::Jmp(0),
+            ],
+            program.code
+        );
+        assert_eq!(Some(0), program.table.slot(&VarName::from("X")));
+        assert_eq!(1, program.table.len());
+        // while が 0 番、本体の代入が 1 番
+        assert_eq!(Some(0), program.entry(0));
+        assert_eq!(Some(0), program.branch(3));
+        assert_eq!(Some(1), program.entry(4));
+        assert_eq!(None, program.entry(5));
+    }
+
+    #[test]
+    fn same_as_interpreter() {
+        for (program, state) in [
+            (
+                "Y := 1; while 1 <= X do { Y := Y * X; X := X - 1 }",
+                State::from(&[("X", 5.into())]),
+            ),
+            (
+                "if not (X = 0 or 3 <= X) and true then Z := 1 else Z := 2",
+                State::from(&[("X", 1.into()), ("W", 7.into())]),
+            ),
+            (
+                "if false and Y = 0 then skip else { if true or Y = 0 then Z := 3 else skip }",
+                State::init(),
+            ),
+        ] {
+            let c = parse_com(program).unwrap();
+            let (_, expected) = c.try_execute(state.clone()).unwrap();
+            assert_eq!(Ok(expected), c.compile().run(state), "{}", program);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let c = parse_com("X := 1; Y := X + Z").unwrap();
+        assert_eq!(
+            Err(VmError::Imp(ImpError::UndefinedVariable("Z".into()))),
+            c.compile().run(State::init())
+        );
+
+        let c = parse_com("X := 2147483647; Y := 2 * (X + 1)").unwrap();
+        assert_eq!(
+            Err(VmError::Imp(ImpError::Overflow(
+                parse_aexp("X + 1").unwrap()
+            ))),
+            c.compile().run(State::init())
+        );
+    }
+
+    #[test]
+    fn malformed_code() {
+        // 誤ってコンパイルされた命令列でもパニックしない
+        let c = parse_com("if 2147483647 <= 2 then X := 1 else X := 2").unwrap();
+        for (at, instr, e) in [
+            (0, Instr::Add, VmError::StackUnderflow),
+            (0, Instr::Load(7), VmError::BadSlot(7)),
+            (2, Instr::Add, VmError::MissingSource),
+        ] {
+            let mut program = c.compile();
+            program.code[at] = instr;
+            assert_eq!(Err(e), program.run(State::init()), "{}", program);
+        }
+    }
+
+    #[test]
+    fn disassemble() {
+        let program = parse_com("X := 0; while X <= 9 do X := X + 1")
+            .unwrap()
+            .compile();
+        assert_eq!(
+            " 0: push 0\n \
+             1: store 0    ; X\n \
+             2: load 0     ; X\n \
+             3: push 9\n \
+             4: le\n \
+             5: jz 11\n \
+             6: load 0     ; X\n \
+             7: push 1\n \
+             8: add\n \
+             9: store 0    ; X\n\
+             10: jmp 2",
+            program.to_string()
+        );
+    }
+
+    #[test]
+    fn trace() {
+        let program = parse_com("if X <= 1 then Y := X * 2 else skip")
+            .unwrap()
+            .compile();
+        let trace = program.trace(State::from(&[("X", 1.into())]), 100);
+        assert_eq!(
+            "0: load 0     [1]\n\
+             1: push 1     [1, 1]\n\
+             2: le         [1]\n\
+             3: jz 9       []\n\
+             4: load 0     [1]\n\
+             5: push 2     [1, 2]\n\
+             6: mul        [2]\n\
+             7: store 1    []\n\
+             8: jmp 9      []\n\
+             halted with {X ↦ 1, Y ↦ 2}",
+            trace.to_string()
+        );
+
+        let trace = program.trace(State::init(), 100);
+        assert!(trace.steps.is_empty());
+        assert_eq!(
+            Some(Err(VmError::Imp(ImpError::UndefinedVariable("X".into())))),
+            trace.result
+        );
+        assert_eq!(
+            None,
+            program.trace(State::from(&[("X", 1.into())]), 3).result
+        );
+    }
+
+    #[test]
+    fn long_while_loop() {
+        let c = parse_com("while X <= 999999 do X := X + 1").unwrap();
+        let state = c.compile().run(State::from(&[("X", 0.into())])).unwrap();
+        assert_eq!(&Some(Number(1_000_000)), state.get(&"X".into()));
+    }
+}
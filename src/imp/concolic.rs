@@ -0,0 +1,424 @@
+//! コンコリックテスト
+//!
+//! 入力変数に具体的な値を与えてコマンドを実行しながら、通った分岐の条件を入力変数の式として記録します。
+//! 記録した条件の列のどこか 1 つを否定した条件を満たす入力を有限の範囲で探し、
+//! 見つかればその入力で実行し直す、ということを繰り返して、
+//! 各 `if`/`while` の条件が成り立つ側と成り立たない側の両方を通る入力を集めます。
+//!
+//! 入力の探索は範囲内の値をすべて試すだけなので、範囲の外にしか入力がない分岐や、
+//! 実行不可能な分岐は網羅されずに残ります。
+
+use std::{fmt, ops::RangeInclusive};
+
+use crate::{
+    imp::{bounded::DEFAULT_BOX, random::DEFAULT_FUEL, Aexp, Bexp, Com},
+    State, TryEvaluate, TryEvaluateRef, VarName,
+};
+
+/// コンコリックテストの設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct Concolic {
+    /// 入力変数
+    pub inputs: Vec<VarName>,
+    /// 入力変数の値を探す範囲
+    pub domain: RangeInclusive<i32>,
+    /// 否定を試す分岐の、実行の先頭からの個数の上限
+    pub depth: usize,
+    /// 1 回の実行で与える燃料
+    pub fuel: u64,
+}
+
+impl Concolic {
+    /// 入力変数 `inputs` について、既定の設定で作ります。
+    pub fn new(inputs: &[&str]) -> Concolic {
+        Concolic {
+            inputs: inputs.iter().map(|&x| x.into()).collect(),
+            domain: DEFAULT_BOX,
+            depth: 10,
+            fuel: DEFAULT_FUEL,
+        }
+    }
+
+    /// `c` の分岐を網羅する初期状態を生成します。
+    ///
+    /// 生成した初期状態のうち、新しい分岐を通ったものだけを順に返します。
+    /// 入力変数の値を探す範囲が空ならエラーを返します。
+    pub fn generate(&self, c: &Com) -> Result<Report, EmptyDomain> {
+        if self.domain.is_empty() {
+            return Err(EmptyDomain(self.domain.clone()));
+        }
+        let mut coverage = Coverage::new(c);
+        let mut tests = vec![];
+
+        let start = self.input(&vec![
+            0.clamp(*self.domain.start(), *self.domain.end());
+            self.inputs.len()
+        ]);
+        let mut work = vec![(start, 0)];
+        while let Some((input, bound)) = work.pop() {
+            let mut decisions = vec![];
+            let mut fuel = self.fuel;
+            trace(
+                c,
+                0,
+                input.clone(),
+                State::init(),
+                &mut fuel,
+                &mut decisions,
+            );
+
+            if coverage.record(&decisions) {
+                tests.push(input);
+            }
+            if coverage.is_complete() {
+                break;
+            }
+
+            // bound より前の分岐は、この入力を生成したときに否定を試している
+            for i in bound..decisions.len().min(self.depth) {
+                let flipped = Bexp::not(decisions[i].condition.clone());
+                // 入力によらない条件は否定しても満たせない
+                if flipped.try_evaluate_ref(&State::init()).is_ok() {
+                    continue;
+                }
+                let mut goal: Vec<_> = decisions[..i].iter().map(|d| d.condition.clone()).collect();
+                goal.push(flipped);
+                if let Some(next) = self.search(&goal) {
+                    work.push((next, i + 1));
+                }
+            }
+        }
+
+        Ok(Report { tests, coverage })
+    }
+
+    /// 入力変数に `values` を割り当てた状態を返します。
+    fn input(&self, values: &[i32]) -> State {
+        let mut state = State::init();
+        for (x, n) in self.inputs.iter().zip(values) {
+            state = state.update_variable(x, (*n).into());
+        }
+        state
+    }
+
+    /// `goal` の条件がすべて成り立つ入力を、範囲内で最初の入力変数が最も速く変わる順に探します。
+    fn search(&self, goal: &[Bexp]) -> Option<State> {
+        let mut values = vec![*self.domain.start(); self.inputs.len()];
+        loop {
+            let state = self.input(&values);
+            let satisfied = goal
+                .iter()
+                .all(|b| b.try_evaluate_ref(&state).is_ok_and(bool::from));
+            if satisfied {
+                return Some(state);
+            }
+
+            let k = values.iter().position(|n| n < self.domain.end())?;
+            values[k] += 1;
+            values[..k].fill(*self.domain.start());
+        }
+    }
+}
+
+/// [`Concolic::domain`] が空
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmptyDomain(pub RangeInclusive<i32>);
+
+impl fmt::Display for EmptyDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "domain {}..={} is empty", self.0.start(), self.0.end())
+    }
+}
+
+impl std::error::Error for EmptyDomain {}
+
+/// 実行中に通った分岐
+struct Decision {
+    /// 分岐の番号
+    id: usize,
+    /// 条件が成り立ったかどうか
+    taken: bool,
+    /// 通った側の条件を入力変数の式で表したもの
+    condition: Bexp,
+}
+
+/// `id` 番から番号を振った `c` を、具体的な状態 `state` と記号的な状態 `sym` で実行します。
+/// エラーが起きたり燃料を使い切ったりしたら `None` を返します。
+fn trace(
+    c: &Com,
+    id: usize,
+    state: State,
+    sym: State<Aexp>,
+    fuel: &mut u64,
+    decisions: &mut Vec<Decision>,
+) -> Option<(State, State<Aexp>)> {
+    *fuel = fuel.checked_sub(1)?;
+    match c {
+        Com::Skip => Some((state, sym)),
+        Com::Subst(x, a) => {
+            let (n, state) = a.try_evaluate(state).ok()?;
+            let a = a.substitute(&sym);
+            Some((state.update_variable(x, n), sym.update_variable(x, a)))
+        }
+        Com::Seq(c_0, c_1) => {
+            let (state, sym) = trace(c_0, id, state, sym, fuel, decisions)?;
+            trace(c_1, id + count(c_0), state, sym, fuel, decisions)
+        }
+        Com::If(b, c_0, c_1) => {
+            let (t, state) = decide(id, b, state, &sym, decisions)?;
+            if t {
+                trace(c_0, id + 1, state, sym, fuel, decisions)
+            } else {
+                trace(c_1, id + 1 + count(c_0), state, sym, fuel, decisions)
+            }
+        }
+        Com::While(b, body) => {
+            let (mut state, mut sym) = (state, sym);
+            loop {
+                let t;
+                (t, state) = decide(id, b, state, &sym, decisions)?;
+                if !t {
+                    return Some((state, sym));
+                }
+                (state, sym) = trace(body, id + 1, state, sym, fuel, decisions)?;
+                *fuel = fuel.checked_sub(1)?;
+            }
+        }
+    }
+}
+
+/// 分岐 `id` の条件 `b` を評価し、通った側を記録します。
+fn decide(
+    id: usize,
+    b: &Bexp,
+    state: State,
+    sym: &State<Aexp>,
+    decisions: &mut Vec<Decision>,
+) -> Option<(bool, State)> {
+    let (t, state) = b.try_evaluate(state).ok()?;
+    let taken = t.into();
+    let condition = b.substitute(sym);
+    decisions.push(Decision {
+        id,
+        taken,
+        condition: if taken {
+            condition
+        } else {
+            Bexp::not(condition)
+        },
+    });
+    Some((taken, state))
+}
+
+/// `c` に含まれる `if` と `while` の個数
+fn count(c: &Com) -> usize {
+    match c {
+        Com::Skip | Com::Subst(..) => 0,
+        Com::Seq(c_0, c_1) => count(c_0) + count(c_1),
+        Com::If(_, c_0, c_1) => 1 + count(c_0) + count(c_1),
+        Com::While(_, c) => 1 + count(c),
+    }
+}
+
+/// 分岐となる `if` または `while`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchPoint {
+    /// `while` かどうか
+    pub is_loop: bool,
+    /// 条件
+    pub condition: Bexp,
+    /// 条件が成り立つ側を通ったかどうか
+    pub taken: bool,
+    /// 条件が成り立たない側を通ったかどうか
+    pub not_taken: bool,
+}
+
+/// 分岐網羅の状況。分岐は `if` と `while` の出現順に並べます。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coverage {
+    /// 分岐
+    pub points: Vec<BranchPoint>,
+}
+
+impl Coverage {
+    fn new(c: &Com) -> Coverage {
+        fn collect(c: &Com, points: &mut Vec<BranchPoint>) {
+            let point = |is_loop, b: &Bexp| BranchPoint {
+                is_loop,
+                condition: b.clone(),
+                taken: false,
+                not_taken: false,
+            };
+            match c {
+                Com::Skip | Com::Subst(..) => {}
+                Com::Seq(c_0, c_1) => {
+                    collect(c_0, points);
+                    collect(c_1, points);
+                }
+                Com::If(b, c_0, c_1) => {
+                    points.push(point(false, b));
+                    collect(c_0, points);
+                    collect(c_1, points);
+                }
+                Com::While(b, c) => {
+                    points.push(point(true, b));
+                    collect(c, points);
+                }
+            }
+        }
+
+        let mut points = vec![];
+        collect(c, &mut points);
+        Coverage { points }
+    }
+
+    /// 通った分岐を記録し、新しく通った分岐があったかどうかを返します。
+    fn record(&mut self, decisions: &[Decision]) -> bool {
+        let mut new = false;
+        for d in decisions {
+            let point = &mut self.points[d.id];
+            let side = if d.taken {
+                &mut point.taken
+            } else {
+                &mut point.not_taken
+            };
+            new |= !*side;
+            *side = true;
+        }
+        new
+    }
+
+    /// 通った分岐の数
+    pub fn covered(&self) -> usize {
+        self.points
+            .iter()
+            .map(|p| p.taken as usize + p.not_taken as usize)
+            .sum()
+    }
+
+    /// 分岐の総数
+    pub fn total(&self) -> usize {
+        2 * self.points.len()
+    }
+
+    /// すべての分岐を通ったかどうか
+    pub fn is_complete(&self) -> bool {
+        self.covered() == self.total()
+    }
+}
+
+/// 分岐ごとに `if X <= 0: true ✓, false ✗` の形で出力し、最後に網羅した割合を出力します。
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = |covered| if covered { '✓' } else { '✗' };
+        for p in &self.points {
+            writeln!(
+                f,
+                "{} {}: true {}, false {}",
+                if p.is_loop { "while" } else { "if" },
+                p.condition,
+                mark(p.taken),
+                mark(p.not_taken)
+            )?;
+        }
+        write!(f, "{}/{} branches covered", self.covered(), self.total())
+    }
+}
+
+/// [`Concolic::generate`] の結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// 生成した初期状態
+    pub tests: Vec<State>,
+    /// 生成した初期状態すべてで実行したときの分岐網羅の状況
+    pub coverage: Coverage,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            concolic::{Concolic, EmptyDomain},
+            parse_com,
+        },
+        State,
+    };
+
+    #[test]
+    fn cover_nested_branches() {
+        let c = parse_com("if X <= 0 then Y := 0 else { if X * X = 9 then Y := 1 else Y := 2 }")
+            .unwrap();
+        let report = Concolic::new(&["X"]).generate(&c).unwrap();
+        assert_eq!(
+            vec![
+                State::from(&[("X", 0.into())]),
+                State::from(&[("X", 1.into())]),
+                State::from(&[("X", 3.into())]),
+            ],
+            report.tests
+        );
+        assert_eq!(
+            "if X <= 0: true ✓, false ✓\n\
+             if X * X = 9: true ✓, false ✓\n\
+             4/4 branches covered",
+            report.coverage.to_string()
+        );
+    }
+
+    #[test]
+    fn cover_loops() {
+        let c = parse_com(
+            "Z := 0; while 1 <= X do { if Y <= X then Z := Z + 1 else skip; X := X - 1 }",
+        )
+        .unwrap();
+        let report = Concolic::new(&["X", "Y"]).generate(&c).unwrap();
+        assert!(report.coverage.is_complete(), "{}", report.coverage);
+        assert_eq!(
+            State::from(&[("X", 0.into()), ("Y", 0.into())]),
+            report.tests[0]
+        );
+    }
+
+    #[test]
+    fn infeasible_branches_remain() {
+        // X * X は負にならないので、else 側は通らない
+        let c = parse_com("if 0 <= X * X then Y := 1 else Y := 2; while 1 <= 0 do skip").unwrap();
+        let report = Concolic::new(&["X"]).generate(&c).unwrap();
+        assert_eq!(1, report.tests.len());
+        assert_eq!(
+            "if 0 <= X * X: true ✓, false ✗\n\
+             while 1 <= 0: true ✗, false ✓\n\
+             2/4 branches covered",
+            report.coverage.to_string()
+        );
+    }
+
+    #[test]
+    fn search_within_domain() {
+        // 範囲の外にしか入力がない分岐は網羅されない
+        let c = parse_com("if X = 100 then skip else skip").unwrap();
+        let mut concolic = Concolic::new(&["X"]);
+        assert_eq!(1, concolic.generate(&c).unwrap().coverage.covered());
+
+        concolic.domain = 90..=110;
+        let report = concolic.generate(&c).unwrap();
+        assert!(report.coverage.is_complete());
+        assert_eq!(
+            vec![
+                State::from(&[("X", 90.into())]),
+                State::from(&[("X", 100.into())]),
+            ],
+            report.tests
+        );
+    }
+
+    #[test]
+    fn empty_domain() {
+        let c = parse_com("if X = 0 then skip else skip").unwrap();
+        let mut concolic = Concolic::new(&["X"]);
+        #[allow(clippy::reversed_empty_ranges)]
+        let domain = 5..=4;
+        concolic.domain = domain.clone();
+        assert_eq!(Err(EmptyDomain(domain)), concolic.generate(&c));
+    }
+}
@@ -0,0 +1,279 @@
+//! IMP の表示的意味論
+//!
+//! 式とコマンドの意味を、状態上の部分関数として構成的に定めます。
+//!
+//! ```text
+//! 𝒜⟦n⟧ = {(σ, n)}
+//! 𝒜⟦X⟧ = {(σ, σ(X))}
+//! 𝒜⟦a_0 + a_1⟧ = {(σ, n_0 + n_1) | (σ, n_0) ∈ 𝒜⟦a_0⟧, (σ, n_1) ∈ 𝒜⟦a_1⟧}
+//!
+//! 𝒞⟦skip⟧ = {(σ, σ)}
+//! 𝒞⟦X := a⟧ = {(σ, σ[n/X]) | (σ, n) ∈ 𝒜⟦a⟧}
+//! 𝒞⟦c_0; c_1⟧ = 𝒞⟦c_1⟧ ∘ 𝒞⟦c_0⟧
+//! 𝒞⟦if b then c_0 else c_1⟧ = {(σ, σ') | ℬ⟦b⟧σ = true, (σ, σ') ∈ 𝒞⟦c_0⟧}
+//!                           ∪ {(σ, σ') | ℬ⟦b⟧σ = false, (σ, σ') ∈ 𝒞⟦c_1⟧}
+//! 𝒞⟦while b do c⟧ = fix(Γ)
+//!
+//! Γ(φ) = {(σ, σ') | ℬ⟦b⟧σ = true, (σ, σ') ∈ φ ∘ 𝒞⟦c⟧}
+//!      ∪ {(σ, σ) | ℬ⟦b⟧σ = false}
+//! ```
+//!
+//! `fix(Γ)` は空の部分関数 `⊥` から始めた列 `Γ⁰(⊥) ⊆ Γ¹(⊥) ⊆ Γ²(⊥) ⊆ ...` の和です。
+//! 無限に続けることはできないので、`Γⁿ(⊥)` を上限 `n` で打ち切って近似します。
+//!
+//! 未定義の変数の参照やオーバーフローがあると、その状態で値が定義されません。
+
+use std::rc::Rc;
+
+use crate::{
+    imp::{Aexp, Bexp, BexpImpl, Com},
+    Number, State, Truth,
+};
+
+/// 状態上の部分関数
+///
+/// 値が定義されない状態では `None` を返します。
+pub type Denotation<T> = Rc<dyn Fn(&State) -> Option<T>>;
+
+/// `while` の意味を近似するときの `n` の既定値
+pub const DEFAULT_BOUND: u64 = 1 << 20;
+
+impl Aexp {
+    /// 𝒜⟦a⟧ を返します。
+    pub fn denote(&self) -> Denotation<Number> {
+        let (left, right, op): (_, _, fn(Number, Number) -> Option<Number>) = match self {
+            Aexp::N(n) => {
+                let n = *n;
+                return Rc::new(move |_| Some(n));
+            }
+            Aexp::Loc(var) => {
+                let var = var.clone();
+                return Rc::new(move |state| *state.get(&var));
+            }
+            Aexp::Add(a0, a1) => (a0.denote(), a1.denote(), Number::checked_add),
+            Aexp::Sub(a0, a1) => (a0.denote(), a1.denote(), Number::checked_sub),
+            Aexp::Mul(a0, a1) => (a0.denote(), a1.denote(), Number::checked_mul),
+        };
+        Rc::new(move |state| op(left(state)?, right(state)?))
+    }
+}
+
+impl Bexp {
+    /// ℬ⟦b⟧ を返します。
+    pub fn denote(&self) -> Denotation<Truth> {
+        self.bexp.denote()
+    }
+}
+
+impl BexpImpl {
+    fn denote(&self) -> Denotation<Truth> {
+        match self {
+            BexpImpl::T(t) => {
+                let t = *t;
+                Rc::new(move |_| Some(t))
+            }
+            BexpImpl::Eq(a0, a1) => {
+                let (a0, a1) = (a0.denote(), a1.denote());
+                Rc::new(move |state| Some((a0(state)? == a1(state)?).into()))
+            }
+            BexpImpl::Le(a0, a1) => {
+                let (a0, a1) = (a0.denote(), a1.denote());
+                Rc::new(move |state| Some((a0(state)? <= a1(state)?).into()))
+            }
+            BexpImpl::Not(b) => {
+                let b = b.denote();
+                Rc::new(move |state| Some(!b(state)?))
+            }
+            // 短絡評価は操作的意味論と同じ
+            BexpImpl::And(b0, b1) => {
+                let (b0, b1) = (b0.denote(), b1.denote());
+                Rc::new(move |state| match bool::from(b0(state)?) {
+                    false => Some(false.into()),
+                    true => b1(state),
+                })
+            }
+            BexpImpl::Or(b0, b1) => {
+                let (b0, b1) = (b0.denote(), b1.denote());
+                Rc::new(move |state| match bool::from(b0(state)?) {
+                    true => Some(true.into()),
+                    false => b1(state),
+                })
+            }
+            BexpImpl::Dummy => Rc::new(|_| panic!()), // 短絡評価のテスト用
+        }
+    }
+}
+
+impl Com {
+    /// 𝒞⟦c⟧ を返します。`while` は `Γⁿ(⊥)`（`n` は [`DEFAULT_BOUND`]）で近似します。
+    pub fn denote(&self) -> Denotation<State> {
+        self.denote_with_bound(DEFAULT_BOUND)
+    }
+
+    /// 𝒞⟦c⟧ を返します。`while` は `Γⁿ(⊥)`（`n` は `bound`）で近似します。
+    pub fn denote_with_bound(&self, bound: u64) -> Denotation<State> {
+        match self {
+            Com::Skip => Rc::new(|state| Some(state.clone())),
+            Com::Subst(var, a) => {
+                let (var, a) = (var.clone(), a.denote());
+                Rc::new(move |state| Some(state.clone().update_variable(&var, a(state)?)))
+            }
+            Com::Seq(c0, c1) => {
+                let (c0, c1) = (c0.denote_with_bound(bound), c1.denote_with_bound(bound));
+                Rc::new(move |state| c1(&c0(state)?))
+            }
+            Com::If(b, c0, c1) => {
+                let b = b.denote();
+                let (c0, c1) = (c0.denote_with_bound(bound), c1.denote_with_bound(bound));
+                Rc::new(move |state| match bool::from(b(state)?) {
+                    true => c0(state),
+                    false => c1(state),
+                })
+            }
+            Com::While(b, c) => approximant(b.denote(), c.denote_with_bound(bound), bound),
+        }
+    }
+}
+
+/// どの状態でも定義されない部分関数 `⊥` を返します。
+pub fn bottom() -> Denotation<State> {
+    Rc::new(|_| None)
+}
+
+/// `while b do c` の汎関数 Γ を `φ` に適用した `Γ(φ)` を返します。
+/// `b` と `c` にはそれぞれ ℬ⟦b⟧ と 𝒞⟦c⟧ を渡します。
+pub fn gamma(
+    b: Denotation<Truth>,
+    c: Denotation<State>,
+    phi: Denotation<State>,
+) -> Denotation<State> {
+    Rc::new(move |state| match bool::from(b(state)?) {
+        true => phi(&c(state)?),
+        false => Some(state.clone()),
+    })
+}
+
+/// `Γⁿ(⊥)` を返します。
+///
+/// [`gamma`] を `n` 回重ねると呼び出しが `n` 段入れ子になるので、
+/// 同じ部分関数をループで計算します。
+/// `Γⁿ(⊥)` は、ループ本体を高々 `n - 1` 回実行して `b` が偽になる状態でだけ定義されます。
+pub fn approximant(b: Denotation<Truth>, c: Denotation<State>, n: u64) -> Denotation<State> {
+    Rc::new(move |state| {
+        let mut state = state.clone();
+        for _ in 0..n {
+            if !bool::from(b(&state)?) {
+                return Some(state);
+            }
+            state = c(&state)?;
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            denotational::{approximant, bottom, gamma},
+            parse_aexp, parse_bexp, parse_com,
+        },
+        Evaluate, Execute, State,
+    };
+
+    fn execute(program: &str, state: State) -> State {
+        let (None, state) = parse_com(program).unwrap().execute(state) else {
+            panic!()
+        };
+        state
+    }
+
+    #[test]
+    fn denote_expressions() {
+        let state = State::from(&[("X", 3.into()), ("Y", 4.into())]);
+        for a in ["X + Y * 2", "X - Y", "(X - Y) * -5"] {
+            let a = parse_aexp(a).unwrap();
+            assert_eq!(Some(a.evaluate(state.clone()).0), a.denote()(&state));
+        }
+        for b in [
+            "X <= Y",
+            "not X = 3",
+            "X = 3 and Y <= 3",
+            "(false or Y = 4) and true",
+        ] {
+            let b = parse_bexp(b).unwrap();
+            assert_eq!(Some(b.evaluate(state.clone()).0), b.denote()(&state));
+        }
+
+        assert_eq!(None, parse_aexp("Z + 1").unwrap().denote()(&state));
+        assert_eq!(None, parse_aexp("2147483647 + X").unwrap().denote()(&state));
+        // 短絡評価で右側を参照しない
+        assert_eq!(
+            Some(false.into()),
+            parse_bexp("false and Z = 0").unwrap().denote()(&state)
+        );
+    }
+
+    #[test]
+    fn agrees_with_execute() {
+        let programs = [
+            "skip",
+            "X := X + 1; Y := X * 2",
+            "if X <= Y then Z := Y - X else Z := X - Y",
+            "Z := 1; while 1 <= X do { Z := Z * X; X := X - 1 }",
+            "while not X = Y do if X <= Y then Y := Y - X else X := X - Y",
+            "Z := 0; while 1 <= X do { Y := X; while 1 <= Y do { Z := Z + 1; Y := Y - 1 }; X := X - 1 }",
+        ];
+        for program in programs {
+            for (x, y) in [(0, 0), (1, 5), (6, 4), (12, 18)] {
+                let state = State::from(&[("X", x.into()), ("Y", y.into()), ("Z", 0.into())]);
+                if program.starts_with("while not") && (x == 0 || y == 0) {
+                    continue; // 停止しない
+                }
+                assert_eq!(
+                    Some(execute(program, state.clone())),
+                    parse_com(program).unwrap().denote()(&state),
+                    "{} at {}",
+                    program,
+                    state,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn while_is_least_fixed_point() {
+        let Some(crate::imp::Com::While(b, c)) = parse_com("while 1 <= X do X := X - 1").ok()
+        else {
+            panic!()
+        };
+        let (b, c) = (b.denote(), c.denote());
+
+        let mut phi = bottom();
+        for n in 0..5 {
+            let approx = approximant(b.clone(), c.clone(), n);
+            for x in 0..5 {
+                let state = State::from(&[("X", x.into())]);
+                // Γⁿ(⊥) は本体を n - 1 回以下実行して止まる状態でだけ定義される
+                let expected = (x < n as i32).then(|| State::from(&[("X", 0.into())]));
+                assert_eq!(expected, phi(&state), "Γ^{}(⊥) at {}", n, state);
+                assert_eq!(expected, approx(&state));
+            }
+            phi = gamma(b.clone(), c.clone(), phi);
+        }
+    }
+
+    #[test]
+    fn diverging_loop_is_undefined() {
+        let c = parse_com("while true do X := X + 1").unwrap();
+        let state = State::from(&[("X", 0.into())]);
+        assert_eq!(None, c.denote_with_bound(1000)(&state));
+
+        let c = parse_com("while X <= 9 do X := X + 1").unwrap();
+        assert_eq!(None, c.denote_with_bound(10)(&state));
+        assert_eq!(
+            Some(State::from(&[("X", 10.into())])),
+            c.denote_with_bound(11)(&state)
+        );
+    }
+}
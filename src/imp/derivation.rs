@@ -0,0 +1,580 @@
+//! IMP の big-step 評価の導出木
+//!
+//! [`Evaluate`](crate::Evaluate) や [`Execute`](crate::Execute) と同じ規則で評価しながら、
+//! どの規則をどの前提から適用したかを木として記録します。
+//! 記録した木は [`Derivation::check`] で規則に沿っているかを確かめられます。
+
+use std::fmt;
+
+use crate::{
+    arith::{Arithmetic, Checked},
+    imp::{Aexp, Bexp, BexpImpl, Com, ImpError},
+    Number, State, Truth,
+};
+
+/// 評価の判断
+#[derive(Debug, Clone, PartialEq)]
+pub enum Judgement {
+    /// `⟨a, σ⟩ → n`
+    Aexp(Aexp, State, Number),
+    /// `⟨b, σ⟩ → t`
+    Bexp(Bexp, State, Truth),
+    /// `⟨c, σ⟩ → σ'`
+    Com(Com, State, State),
+}
+
+impl fmt::Display for Judgement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Judgement::Aexp(a, state, n) => write!(f, "⟨{}, {}⟩ → ⟨{}, {}⟩", a, state, n, state),
+            Judgement::Bexp(b, state, t) => write!(f, "⟨{}, {}⟩ → ⟨{}, {}⟩", b, state, t, state),
+            Judgement::Com(c, state, after) => write!(f, "⟨{}, {}⟩ → ⟨(), {}⟩", c, state, after),
+        }
+    }
+}
+
+/// 評価規則
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// `⟨n, σ⟩ → n`
+    Num,
+    /// `⟨X, σ⟩ → σ(X)`
+    Loc,
+    /// `⟨a_0 + a_1, σ⟩ → n_0 + n_1`
+    Sum,
+    /// `⟨a_0 - a_1, σ⟩ → n_0 - n_1`
+    Subt,
+    /// `⟨a_0 * a_1, σ⟩ → n_0 × n_1`
+    Prod,
+    /// `⟨true, σ⟩ → true`
+    True,
+    /// `⟨false, σ⟩ → false`
+    False,
+    /// `⟨a_0 = a_1, σ⟩ → true`（`n_0 = n_1`）
+    EqTrue,
+    /// `⟨a_0 = a_1, σ⟩ → false`（`n_0 ≠ n_1`）
+    EqFalse,
+    /// `⟨a_0 <= a_1, σ⟩ → true`（`n_0 ≤ n_1`）
+    LeTrue,
+    /// `⟨a_0 <= a_1, σ⟩ → false`（`n_0 > n_1`）
+    LeFalse,
+    /// `⟨not b, σ⟩ → ¬t`
+    Not,
+    /// `⟨b_0 and b_1, σ⟩ → false`（`b_0` が偽なら `b_1` は評価しない）
+    AndFalse,
+    /// `⟨b_0 and b_1, σ⟩ → t_1`（`b_0` が真）
+    AndTrue,
+    /// `⟨b_0 or b_1, σ⟩ → true`（`b_0` が真なら `b_1` は評価しない）
+    OrTrue,
+    /// `⟨b_0 or b_1, σ⟩ → t_1`（`b_0` が偽）
+    OrFalse,
+    /// `⟨skip, σ⟩ → σ`
+    Skip,
+    /// `⟨X := a, σ⟩ → σ[n/X]`
+    Assign,
+    /// `⟨c_0; c_1, σ⟩ → σ'`
+    Seq,
+    /// `⟨if b then c_0 else c_1, σ⟩ → σ'`（`b` が真）
+    IfTrue,
+    /// `⟨if b then c_0 else c_1, σ⟩ → σ'`（`b` が偽）
+    IfFalse,
+    /// `⟨while b do c, σ⟩ → σ`（`b` が偽）
+    WhileFalse,
+    /// `⟨while b do c, σ⟩ → σ'`（`b` が真）
+    WhileTrue,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Rule::Num => "num",
+            Rule::Loc => "loc",
+            Rule::Sum => "sum",
+            Rule::Subt => "subt",
+            Rule::Prod => "prod",
+            Rule::True => "true",
+            Rule::False => "false",
+            Rule::EqTrue => "eq-true",
+            Rule::EqFalse => "eq-false",
+            Rule::LeTrue => "le-true",
+            Rule::LeFalse => "le-false",
+            Rule::Not => "not",
+            Rule::AndFalse => "and-false",
+            Rule::AndTrue => "and-true",
+            Rule::OrTrue => "or-true",
+            Rule::OrFalse => "or-false",
+            Rule::Skip => "skip",
+            Rule::Assign => "assign",
+            Rule::Seq => "seq",
+            Rule::IfTrue => "if-true",
+            Rule::IfFalse => "if-false",
+            Rule::WhileFalse => "while-false",
+            Rule::WhileTrue => "while-true",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 導出木
+///
+/// ```text
+/// premises[0]  premises[1]  ...
+/// ----------------------------- rule
+///          conclusion
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Derivation {
+    /// 最後に適用した規則
+    pub rule: Rule,
+    /// 前提の導出木
+    pub premises: Vec<Derivation>,
+    /// 結論
+    pub conclusion: Judgement,
+}
+
+impl Derivation {
+    fn new(rule: Rule, premises: Vec<Derivation>, conclusion: Judgement) -> Derivation {
+        Derivation {
+            rule,
+            premises,
+            conclusion,
+        }
+    }
+
+    /// 前提なしで結論を導く規則を適用した導出木（公理）かどうかを返します。
+    pub fn is_axiom(&self) -> bool {
+        self.premises.is_empty()
+    }
+
+    /// 結論の算術式の値を返します。
+    fn number(&self) -> Option<Number> {
+        match &self.conclusion {
+            Judgement::Aexp(_, _, n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// 結論のブール式の値を返します。
+    fn truth(&self) -> Option<bool> {
+        match &self.conclusion {
+            Judgement::Bexp(_, _, t) => Some((*t).into()),
+            _ => None,
+        }
+    }
+
+    /// 各節点で、規則の形と前提・結論が合っているかを確かめます。
+    /// 規則に沿わない節点があれば、最初に見つかったものを返します。
+    pub fn check(&self) -> Result<(), &Derivation> {
+        if !self.is_valid_step() {
+            return Err(self);
+        }
+        self.premises.iter().try_for_each(Derivation::check)
+    }
+
+    /// この節点だけについて、規則の適用が正しいかを返します。
+    fn is_valid_step(&self) -> bool {
+        match &self.conclusion {
+            Judgement::Aexp(a, state, n) => self.is_valid_aexp_step(a, state, *n),
+            Judgement::Bexp(b, state, t) => self.is_valid_bexp_step(&b.bexp, state, (*t).into()),
+            Judgement::Com(c, state, after) => self.is_valid_com_step(c, state, after),
+        }
+    }
+
+    /// 前提 `i` が `⟨a, σ⟩` の評価なら、その値を返します。
+    fn aexp_premise(&self, i: usize, a: &Aexp, state: &State) -> Option<Number> {
+        match &self.premises.get(i)?.conclusion {
+            Judgement::Aexp(e, s, n) if e == a && s == state => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// 前提 `i` が `⟨b, σ⟩` の評価なら、その値を返します。
+    fn bexp_premise(&self, i: usize, b: &BexpImpl, state: &State) -> Option<bool> {
+        match &self.premises.get(i)?.conclusion {
+            Judgement::Bexp(e, s, t) if &e.bexp == b && s == state => Some((*t).into()),
+            _ => None,
+        }
+    }
+
+    /// 前提 `i` が `⟨c, σ⟩` の実行なら、実行後の状態を返します。
+    fn com_premise(&self, i: usize, c: &Com, state: &State) -> Option<&State> {
+        match &self.premises.get(i)?.conclusion {
+            Judgement::Com(e, s, after) if e == c && s == state => Some(after),
+            _ => None,
+        }
+    }
+
+    fn is_valid_aexp_step(&self, a: &Aexp, state: &State, n: Number) -> bool {
+        let arity = self.premises.len();
+        let (a_0, a_1, op): (_, _, fn(_, _) -> _) = match (self.rule, a) {
+            (Rule::Num, Aexp::N(m)) => return arity == 0 && n == *m,
+            (Rule::Loc, Aexp::Loc(var)) => return arity == 0 && state.get(var) == &Some(n),
+            (Rule::Sum, Aexp::Add(a_0, a_1)) => (a_0, a_1, Checked::add),
+            (Rule::Subt, Aexp::Sub(a_0, a_1)) => (a_0, a_1, Checked::sub),
+            (Rule::Prod, Aexp::Mul(a_0, a_1)) => (a_0, a_1, Checked::mul),
+            _ => return false,
+        };
+        match (
+            self.aexp_premise(0, a_0, state),
+            self.aexp_premise(1, a_1, state),
+        ) {
+            (Some(n_0), Some(n_1)) => arity == 2 && op(n_0, n_1) == Some(n),
+            _ => false,
+        }
+    }
+
+    fn is_valid_bexp_step(&self, b: &BexpImpl, state: &State, t: bool) -> bool {
+        let arity = self.premises.len();
+        match (self.rule, b) {
+            (Rule::True, BexpImpl::T(Truth(true))) | (Rule::False, BexpImpl::T(Truth(false))) => {
+                arity == 0 && t == (self.rule == Rule::True)
+            }
+            (Rule::EqTrue | Rule::EqFalse, BexpImpl::Eq(a_0, a_1))
+            | (Rule::LeTrue | Rule::LeFalse, BexpImpl::Le(a_0, a_1)) => {
+                let (Some(n_0), Some(n_1)) = (
+                    self.aexp_premise(0, a_0, state),
+                    self.aexp_premise(1, a_1, state),
+                ) else {
+                    return false;
+                };
+                let holds = match self.rule {
+                    Rule::EqTrue | Rule::EqFalse => n_0 == n_1,
+                    _ => n_0 <= n_1,
+                };
+                let expected = matches!(self.rule, Rule::EqTrue | Rule::LeTrue);
+                arity == 2 && holds == expected && t == expected
+            }
+            (Rule::Not, BexpImpl::Not(b)) => {
+                arity == 1 && self.bexp_premise(0, b, state) == Some(!t)
+            }
+            (Rule::AndFalse, BexpImpl::And(b_0, _)) | (Rule::OrTrue, BexpImpl::Or(b_0, _)) => {
+                let expected = self.rule == Rule::OrTrue;
+                arity == 1 && self.bexp_premise(0, b_0, state) == Some(expected) && t == expected
+            }
+            (Rule::AndTrue, BexpImpl::And(b_0, b_1)) | (Rule::OrFalse, BexpImpl::Or(b_0, b_1)) => {
+                arity == 2
+                    && self.bexp_premise(0, b_0, state) == Some(self.rule == Rule::AndTrue)
+                    && self.bexp_premise(1, b_1, state) == Some(t)
+            }
+            _ => false,
+        }
+    }
+
+    fn is_valid_com_step(&self, c: &Com, state: &State, after: &State) -> bool {
+        let arity = self.premises.len();
+        match (self.rule, c) {
+            (Rule::Skip, Com::Skip) => arity == 0 && state == after,
+            (Rule::Assign, Com::Subst(var, a)) => {
+                arity == 1
+                    && self
+                        .aexp_premise(0, a, state)
+                        .is_some_and(|n| *after == state.clone().update_variable(var, n))
+            }
+            (Rule::Seq, Com::Seq(c_0, c_1)) => {
+                arity == 2
+                    && self
+                        .com_premise(0, c_0, state)
+                        .is_some_and(|middle| self.com_premise(1, c_1, middle) == Some(after))
+            }
+            (Rule::IfTrue | Rule::IfFalse, Com::If(b, c_0, c_1)) => {
+                let t = self.rule == Rule::IfTrue;
+                arity == 2
+                    && self.bexp_premise(0, &b.bexp, state) == Some(t)
+                    && self.com_premise(1, if t { c_0 } else { c_1 }, state) == Some(after)
+            }
+            (Rule::WhileFalse, Com::While(b, _)) => {
+                arity == 1 && self.bexp_premise(0, &b.bexp, state) == Some(false) && state == after
+            }
+            (Rule::WhileTrue, Com::While(b, body)) => {
+                arity == 3
+                    && self.bexp_premise(0, &b.bexp, state) == Some(true)
+                    && self
+                        .com_premise(1, body, state)
+                        .is_some_and(|middle| self.com_premise(2, c, middle) == Some(after))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Aexp {
+    /// 与えられた状態で自身を評価し、その導出木を返します。
+    pub fn derive(&self, state: &State) -> Result<Derivation, ImpError> {
+        let conclude = |rule, premises, n| {
+            Derivation::new(
+                rule,
+                premises,
+                Judgement::Aexp(self.clone(), state.clone(), n),
+            )
+        };
+        let (rule, a_0, a_1, op): (_, _, _, fn(_, _) -> _) = match self {
+            Aexp::N(n) => return Ok(conclude(Rule::Num, vec![], *n)),
+            Aexp::Loc(var) => {
+                return match state.get(var) {
+                    Some(n) => Ok(conclude(Rule::Loc, vec![], *n)),
+                    None => Err(ImpError::UndefinedVariable(var.to_owned())),
+                }
+            }
+            Aexp::Add(a_0, a_1) => (Rule::Sum, a_0, a_1, Checked::add),
+            Aexp::Sub(a_0, a_1) => (Rule::Subt, a_0, a_1, Checked::sub),
+            Aexp::Mul(a_0, a_1) => (Rule::Prod, a_0, a_1, Checked::mul),
+        };
+        let d_0 = a_0.derive(state)?;
+        let d_1 = a_1.derive(state)?;
+        let n = op(d_0.number().unwrap(), d_1.number().unwrap())
+            .ok_or_else(|| ImpError::Overflow(self.clone()))?;
+        Ok(conclude(rule, vec![d_0, d_1], n))
+    }
+}
+
+impl Bexp {
+    /// 与えられた状態で自身を評価し、その導出木を返します。
+    pub fn derive(&self, state: &State) -> Result<Derivation, ImpError> {
+        let conclude = |rule, premises, t: bool| {
+            Derivation::new(
+                rule,
+                premises,
+                Judgement::Bexp(self.clone(), state.clone(), t.into()),
+            )
+        };
+        let sub = |bexp: &BexpImpl| Bexp { bexp: bexp.clone() };
+        Ok(match &self.bexp {
+            BexpImpl::T(Truth(true)) => conclude(Rule::True, vec![], true),
+            BexpImpl::T(Truth(false)) => conclude(Rule::False, vec![], false),
+            BexpImpl::Eq(a_0, a_1) | BexpImpl::Le(a_0, a_1) => {
+                let d_0 = a_0.derive(state)?;
+                let d_1 = a_1.derive(state)?;
+                let (n_0, n_1) = (d_0.number(), d_1.number());
+                let (t, rule) = match &self.bexp {
+                    BexpImpl::Eq(..) if n_0 == n_1 => (true, Rule::EqTrue),
+                    BexpImpl::Eq(..) => (false, Rule::EqFalse),
+                    _ if n_0 <= n_1 => (true, Rule::LeTrue),
+                    _ => (false, Rule::LeFalse),
+                };
+                conclude(rule, vec![d_0, d_1], t)
+            }
+            BexpImpl::Not(b) => {
+                let d = sub(b).derive(state)?;
+                let t = !d.truth().unwrap();
+                conclude(Rule::Not, vec![d], t)
+            }
+            BexpImpl::And(b_0, b_1) => {
+                let d_0 = sub(b_0).derive(state)?;
+                if !d_0.truth().unwrap() {
+                    conclude(Rule::AndFalse, vec![d_0], false)
+                } else {
+                    let d_1 = sub(b_1).derive(state)?;
+                    let t = d_1.truth().unwrap();
+                    conclude(Rule::AndTrue, vec![d_0, d_1], t)
+                }
+            }
+            BexpImpl::Or(b_0, b_1) => {
+                let d_0 = sub(b_0).derive(state)?;
+                if d_0.truth().unwrap() {
+                    conclude(Rule::OrTrue, vec![d_0], true)
+                } else {
+                    let d_1 = sub(b_1).derive(state)?;
+                    let t = d_1.truth().unwrap();
+                    conclude(Rule::OrFalse, vec![d_0, d_1], t)
+                }
+            }
+            BexpImpl::Dummy => panic!(), // 短絡評価のテスト用
+        })
+    }
+}
+
+impl Com {
+    /// 与えられた状態で自身を実行し、その導出木を返します。
+    ///
+    /// [`Execute::execute`](crate::Execute::execute) と同様に、停止しないプログラムでは返りません。
+    pub fn derive(&self, state: &State) -> Result<Derivation, ImpError> {
+        let conclude = |rule, premises, after| {
+            Derivation::new(
+                rule,
+                premises,
+                Judgement::Com(self.clone(), state.clone(), after),
+            )
+        };
+        Ok(match self {
+            Com::Skip => conclude(Rule::Skip, vec![], state.clone()),
+            Com::Subst(var, a) => {
+                let d = a.derive(state)?;
+                let after = state.clone().update_variable(var, d.number().unwrap());
+                conclude(Rule::Assign, vec![d], after)
+            }
+            Com::Seq(c_0, c_1) => {
+                let d_0 = c_0.derive(state)?;
+                let d_1 = c_1.derive(final_state(&d_0))?;
+                let after = final_state(&d_1).clone();
+                conclude(Rule::Seq, vec![d_0, d_1], after)
+            }
+            Com::If(b, c_0, c_1) => {
+                let d_b = b.derive(state)?;
+                let (rule, c) = if d_b.truth().unwrap() {
+                    (Rule::IfTrue, c_0)
+                } else {
+                    (Rule::IfFalse, c_1)
+                };
+                let d_c = c.derive(state)?;
+                let after = final_state(&d_c).clone();
+                conclude(rule, vec![d_b, d_c], after)
+            }
+            Com::While(b, c) => {
+                let d_b = b.derive(state)?;
+                if !d_b.truth().unwrap() {
+                    conclude(Rule::WhileFalse, vec![d_b], state.clone())
+                } else {
+                    let d_c = c.derive(state)?;
+                    let d_w = self.derive(final_state(&d_c))?;
+                    let after = final_state(&d_w).clone();
+                    conclude(Rule::WhileTrue, vec![d_b, d_c, d_w], after)
+                }
+            }
+        })
+    }
+}
+
+/// コマンドの導出木の結論の実行後の状態を返します。
+fn final_state(d: &Derivation) -> &State {
+    match &d.conclusion {
+        Judgement::Com(_, _, after) => after,
+        _ => unreachable!("not a derivation for a command"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            derivation::{Derivation, Judgement, Rule},
+            parse_aexp, parse_bexp, parse_com,
+        },
+        Execute, State,
+    };
+
+    /// 導出木に現れる規則を行きがけ順に並べます。
+    fn rules(d: &Derivation) -> Vec<Rule> {
+        let mut rules = vec![d.rule];
+        for p in &d.premises {
+            rules.extend(self::rules(p));
+        }
+        rules
+    }
+
+    #[test]
+    fn derive_aexp() {
+        //   ⟨X, σ⟩ → 1  ⟨2, σ⟩ → 2
+        //   ---------------------- sum
+        //     ⟨X + 2, σ⟩ → 3
+        let state = State::from(&[("X", 1.into())]);
+        let d = parse_aexp("X + 2").unwrap().derive(&state).unwrap();
+        assert_eq!(vec![Rule::Sum, Rule::Loc, Rule::Num], rules(&d));
+        assert_eq!(
+            Judgement::Aexp(parse_aexp("X + 2").unwrap(), state, 3.into()),
+            d.conclusion,
+        );
+        assert_eq!(Ok(()), d.check());
+    }
+
+    #[test]
+    fn derive_bexp_short_circuit() {
+        let state = State::init();
+        let d = parse_bexp("1 <= 0 and X = 0")
+            .unwrap()
+            .derive(&state)
+            .unwrap();
+        assert_eq!(
+            vec![Rule::AndFalse, Rule::LeFalse, Rule::Num, Rule::Num],
+            rules(&d),
+        );
+        assert_eq!(Ok(()), d.check());
+
+        let d = parse_bexp("not false or X = 0")
+            .unwrap()
+            .derive(&state)
+            .unwrap();
+        assert_eq!(vec![Rule::OrTrue, Rule::Not, Rule::False], rules(&d));
+        assert_eq!(Ok(()), d.check());
+    }
+
+    #[test]
+    fn derive_com() {
+        let com = parse_com("X := 0; while X <= 1 do X := X + 1").unwrap();
+        let d = com.derive(&State::init()).unwrap();
+        assert_eq!(Ok(()), d.check());
+        assert_eq!(
+            vec![
+                Rule::Seq,
+                Rule::Assign,
+                Rule::Num,
+                Rule::WhileTrue,
+                Rule::LeTrue,
+                Rule::Loc,
+                Rule::Num,
+                Rule::Assign,
+                Rule::Sum,
+                Rule::Loc,
+                Rule::Num,
+                Rule::WhileTrue,
+                Rule::LeTrue,
+                Rule::Loc,
+                Rule::Num,
+                Rule::Assign,
+                Rule::Sum,
+                Rule::Loc,
+                Rule::Num,
+                Rule::WhileFalse,
+                Rule::LeFalse,
+                Rule::Loc,
+                Rule::Num,
+            ],
+            rules(&d),
+        );
+
+        // 結論の状態は execute の結果と一致する
+        let (None, expected) = com.execute(State::init()) else {
+            panic!()
+        };
+        assert_eq!(Judgement::Com(com, State::init(), expected), d.conclusion);
+    }
+
+    #[test]
+    fn check_rejects_invalid_step() {
+        let com = parse_com("X := 1 + 1; skip").unwrap();
+        let mut d = com.derive(&State::init()).unwrap();
+        assert_eq!(Ok(()), d.check());
+
+        // ⟨1 + 1, σ₀⟩ → 3 と書き換えると、まず assign 規則の結論 σ₀[2/X] と合わなくなる
+        let Judgement::Aexp(_, _, n) = &mut d.premises[0].premises[0].conclusion else {
+            panic!()
+        };
+        *n = 3.into();
+        assert_eq!(Err(&d.premises[0]), d.check());
+
+        // 結論の状態を σ₀[3/X] に揃えても、sum 規則の適用が誤っている
+        let after = State::from(&[("X", 3.into())]);
+        let Judgement::Com(_, _, s) = &mut d.premises[0].conclusion else {
+            panic!()
+        };
+        *s = after.clone();
+        let Judgement::Com(_, _, s) = &mut d.premises[1].conclusion else {
+            panic!()
+        };
+        *s = after.clone();
+        let Judgement::Com(_, before, s) = &mut d.conclusion else {
+            panic!()
+        };
+        *s = after.clone();
+        assert_eq!(&State::init(), before);
+        let Judgement::Com(_, before, _) = &mut d.premises[1].conclusion else {
+            panic!()
+        };
+        *before = after;
+        assert_eq!(Err(&d.premises[0].premises[0]), d.check());
+
+        // 規則名を取り違えた場合
+        let mut d = com.derive(&State::init()).unwrap();
+        d.premises[0].premises[0].rule = Rule::Prod;
+        assert_eq!(Err(&d.premises[0].premises[0]), d.check());
+    }
+}
@@ -0,0 +1,416 @@
+//! コンパイラの正しさの差分テスト
+//!
+//! どのコマンド `c` と初期状態 `σ` についても、`c` をコンパイルした命令列を仮想機械で実行した結果は
+//! インタプリタで `c` を実行した結果と一致するはずです。
+//! これを与えられたプログラムやランダムなプログラムで確かめます。
+//!
+//! 結果が食い違ったら、インタプリタと仮想機械の実行の記録を先頭から比べ、最初に食い違う命令を報告します。
+//! 記録するのは、代入・`if`・`while` を実行し始めたこと、`if`・`while` の条件の値、代入した値です。
+//! 仮想機械では、コンパイラが覚えておいた各コマンドの最初の命令と条件で飛ぶ `jz` の番地から記録を作るので、
+//! 値の変わらない飛び先の誤りも、その飛び先に制御を移した命令として報告できます。
+//! ただし、コマンドの途中に飛び込む誤りは、その後に食い違う代入として報告します。
+//!
+//! 燃料はインタプリタでは規則の適用の回数、仮想機械では命令の数に使うので、
+//! どちらかが燃料を使い切ったときは、結果が一致するかどうか決められません。
+//! この場合は一致とも食い違いとも見なさず、[`Verdict::Inconclusive`] として区別します。
+
+use std::fmt;
+
+use crate::{
+    imp::{
+        bytecode::{Instr, Program, Vm, VmError},
+        random::{shrink, Generator, Rng},
+        Com, ImpError,
+    },
+    Number, State, TryEvaluateRef, VarName,
+};
+
+/// 結果が食い違う入力
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// コマンド
+    pub program: Com,
+    /// コンパイルした命令列
+    pub code: Program,
+    /// 初期状態
+    pub state: State,
+    /// インタプリタでの結果
+    pub expected: Result<State, ImpError>,
+    /// 仮想機械での結果。誤ってコンパイルされた命令列では仮想機械自体のエラーにもなります。
+    pub actual: Result<State, VmError>,
+    /// 最初に食い違う命令の番地
+    pub pc: usize,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn show(result: &Result<State, impl fmt::Display>) -> String {
+            match result {
+                Ok(state) => state.to_string(),
+                Err(e) => format!("error ({})", e),
+            }
+        }
+        writeln!(
+            f,
+            "`{}` from {}: expected {}, got {}",
+            self.program,
+            self.state,
+            show(&self.expected),
+            show(&self.actual)
+        )?;
+        write!(f, "first divergence at {}: ", self.pc)?;
+        match self.code.code.get(self.pc) {
+            Some(instr) => write!(f, "{}", instr),
+            None => f.write_str("end of code"),
+        }
+    }
+}
+
+/// 食い違わなかったときの判定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// 両方が燃料の範囲で止まり、結果が一致した
+    Agree,
+    /// どちらかが燃料を使い切ったので、比べられなかった
+    Inconclusive,
+}
+
+/// `c` をコンパイルした命令列と `c` 自身を、初期状態 `state` から高々 `fuel` ステップずつ実行して比べます。
+pub fn differential(c: &Com, state: &State, fuel: u64) -> Result<Verdict, Box<Divergence>> {
+    compare(c, &c.compile(), state, fuel)
+}
+
+/// `code` が `c` と同じ結果になるかを、初期状態 `state` から高々 `fuel` ステップずつ実行して比べます。
+///
+/// どちらかが燃料を使い切ったときは決着がつかないので [`Verdict::Inconclusive`] を返します。
+pub fn compare(
+    c: &Com,
+    code: &Program,
+    state: &State,
+    fuel: u64,
+) -> Result<Verdict, Box<Divergence>> {
+    let expected = match c.try_execute_with_fuel(state.clone(), fuel) {
+        Ok((_, state)) => Some(Ok(state)),
+        Err(ImpError::OutOfFuel(_)) => None,
+        Err(e) => Some(Err(e)),
+    };
+    let VmRun {
+        events,
+        last_pc,
+        result: actual,
+    } = run_vm(code, state, fuel);
+    let (Some(expected), Some(actual)) = (expected, actual) else {
+        return Ok(Verdict::Inconclusive);
+    };
+    if expected.clone().map_err(VmError::Imp) == actual {
+        return Ok(Verdict::Agree);
+    }
+
+    // 記録が最初に食い違う命令、または仮想機械が止まった命令
+    let reference = reference_events(c, state, events.len() + 1);
+    let pc = events
+        .iter()
+        .zip(reference.iter().map(Some).chain(std::iter::repeat(None)))
+        .find(|((_, event), reference)| Some(event) != *reference)
+        .map_or(last_pc, |((pc, _), _)| *pc);
+    Err(Box::new(Divergence {
+        program: c.clone(),
+        code: code.clone(),
+        state: state.clone(),
+        expected,
+        actual,
+        pc,
+    }))
+}
+
+/// 実行の記録の 1 項目
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    /// 番号のコマンドを実行し始めた
+    Enter(usize),
+    /// 番号の `if`・`while` の条件を評価した
+    Branch(usize, bool),
+    /// 代入した
+    Store(VarName, Number),
+}
+
+/// 仮想機械で実行した記録
+struct VmRun {
+    /// 記録と、その原因となった命令の番地
+    events: Vec<(usize, Event)>,
+    /// 最後に実行した命令の番地
+    last_pc: usize,
+    /// 実行の結果。燃料を使い切ったら `None` です。
+    result: Option<Result<State, VmError>>,
+}
+
+fn run_vm(code: &Program, state: &State, fuel: u64) -> VmRun {
+    let mut vm = Vm::new(code, state);
+    let mut run = VmRun {
+        events: vec![],
+        last_pc: 0,
+        result: None,
+    };
+    for k in 0..fuel {
+        if vm.is_halted() {
+            break;
+        }
+        let pc = vm.pc;
+        if let Some(id) = code.entry(pc) {
+            // 飛び込んだ、あるいは流れ着いた直前の命令が原因
+            let from = if k == 0 { pc } else { run.last_pc };
+            run.events.push((from, Event::Enter(id)));
+        }
+        run.last_pc = pc;
+        let top = vm.stack.last().copied();
+        if let Err(e) = vm.step() {
+            run.result = Some(Err(e));
+            return run;
+        }
+        match code.code[pc] {
+            Instr::Store(slot) => {
+                let n = vm.store.get(slot).expect("stored slot has a value");
+                run.events
+                    .push((pc, Event::Store(code.table.name(slot).clone(), n)));
+            }
+            Instr::Jz(_) => {
+                if let Some(id) = code.branch(pc) {
+                    run.events
+                        .push((pc, Event::Branch(id, top != Some(Number(0)))));
+                }
+            }
+            _ => {}
+        }
+    }
+    if vm.is_halted() {
+        run.result = Some(Ok(vm.state(state.clone())));
+    }
+    run
+}
+
+/// `c` を実行した記録を、先頭から高々 `limit` 項目まで返します。
+///
+/// コマンドの番号はコンパイラと同じく、代入・`if`・`while` を前順に数えたものです。
+fn reference_events(c: &Com, state: &State, limit: usize) -> Vec<Event> {
+    let mut events = vec![];
+    // エラーか `limit` 項目に達したら止まる
+    let _ = record(c, 0, &mut state.clone(), &mut events, limit);
+    events
+}
+
+/// 番号が `id` から始まる `c` を実行して記録します。
+fn record(
+    c: &Com,
+    id: usize,
+    state: &mut State,
+    events: &mut Vec<Event>,
+    limit: usize,
+) -> Result<(), ()> {
+    let push = |events: &mut Vec<Event>, event| {
+        events.push(event);
+        if events.len() < limit {
+            Ok(())
+        } else {
+            Err(())
+        }
+    };
+    match c {
+        Com::Skip => {}
+        Com::Subst(x, a) => {
+            push(events, Event::Enter(id))?;
+            let n = a.try_evaluate_ref(state).map_err(drop)?;
+            state.assign(x, n);
+            push(events, Event::Store(x.clone(), n))?;
+        }
+        Com::Seq(c_0, c_1) => {
+            record(c_0, id, state, events, limit)?;
+            record(c_1, id + size(c_0), state, events, limit)?;
+        }
+        Com::If(b, c_0, c_1) => {
+            push(events, Event::Enter(id))?;
+            let t = b.try_evaluate_ref(state).map_err(drop)?.into();
+            push(events, Event::Branch(id, t))?;
+            if t {
+                record(c_0, id + 1, state, events, limit)?;
+            } else {
+                record(c_1, id + 1 + size(c_0), state, events, limit)?;
+            }
+        }
+        Com::While(b, c) => loop {
+            push(events, Event::Enter(id))?;
+            let t = b.try_evaluate_ref(state).map_err(drop)?.into();
+            push(events, Event::Branch(id, t))?;
+            if !t {
+                break;
+            }
+            record(c, id + 1, state, events, limit)?;
+        },
+    }
+    Ok(())
+}
+
+/// 番号を振る代入・`if`・`while` の数
+fn size(c: &Com) -> usize {
+    match c {
+        Com::Skip => 0,
+        Com::Subst(..) => 1,
+        Com::Seq(c_0, c_1) => size(c_0) + size(c_1),
+        Com::If(_, c_0, c_1) => 1 + size(c_0) + size(c_1),
+        Com::While(_, c) => 1 + size(c),
+    }
+}
+
+/// ランダムな `cases` 個のプログラムと初期状態で、コンパイルした命令列の結果がインタプリタと一致するか確かめます。
+///
+/// 一致しない入力が見つかったら、食い違いが残る範囲でプログラムを小さくして返します。
+/// すべて食い違わなければ、決着がつかなかった入力の数を返します。
+pub fn check_compiler(
+    generator: &Generator,
+    cases: usize,
+    seed: u64,
+    fuel: u64,
+) -> Result<usize, Box<Divergence>> {
+    let mut rng = Rng::new(seed);
+    let mut inconclusive = 0;
+    for _ in 0..cases {
+        let program = generator.com(&mut rng);
+        let state = generator.state(&mut rng);
+        match differential(&program, &state, fuel) {
+            Ok(Verdict::Agree) => {}
+            Ok(Verdict::Inconclusive) => inconclusive += 1,
+            Err(_) => {
+                let program = shrink(&program, |c| differential(c, &state, fuel).is_err());
+                return differential(&program, &state, fuel).map(|_| inconclusive);
+            }
+        }
+    }
+    Ok(inconclusive)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            bytecode::{Instr, VmError},
+            differential::{check_compiler, compare, differential, Verdict},
+            parse_com,
+            random::{Generator, DEFAULT_FUEL},
+        },
+        State,
+    };
+
+    #[test]
+    fn hand_written_programs() {
+        for (program, state) in [
+            (
+                "Y := 1; while 1 <= X do { Y := Y * X; X := X - 1 }",
+                State::from(&[("X", 6.into())]),
+            ),
+            (
+                "Z := 0; while not X = 0 do { if Y <= X then { Z := Z + Y; X := X - 1 } else X := X - 1 }",
+                State::from(&[("X", 4.into()), ("Y", 2.into())]),
+            ),
+            (
+                "if X = 0 or Y = 0 then skip else Z := X; while false and Y = 0 do skip",
+                State::from(&[("X", 0.into())]),
+            ),
+            ("X := Y + 1", State::init()),
+            ("X := 2147483647; X := X + 1", State::init()),
+        ] {
+            let c = parse_com(program).unwrap();
+            assert_eq!(
+                Ok(Verdict::Agree),
+                differential(&c, &state, 1000),
+                "{}",
+                program
+            );
+        }
+
+        // 停止しないプログラムは比べられない
+        let c = parse_com("while true do skip").unwrap();
+        assert_eq!(
+            Ok(Verdict::Inconclusive),
+            differential(&c, &State::init(), 1000)
+        );
+    }
+
+    #[test]
+    fn inconclusive_when_only_one_runs_out_of_fuel() {
+        // インタプリタでは 1 回の規則の適用だが、仮想機械では 8 命令かかる
+        let c = parse_com("X := 1 + 2 + 3 + 4").unwrap();
+        assert_eq!(
+            Ok(Verdict::Inconclusive),
+            differential(&c, &State::init(), 1)
+        );
+        assert_eq!(Ok(Verdict::Agree), differential(&c, &State::init(), 8));
+
+        // 燃料が足りていれば食い違いを報告する
+        let mut code = c.compile();
+        code.code[0] = Instr::Push(0.into());
+        assert_eq!(
+            Ok(Verdict::Inconclusive),
+            compare(&c, &code, &State::init(), 1)
+        );
+        assert!(compare(&c, &code, &State::init(), 8).is_err());
+    }
+
+    #[test]
+    fn random_programs() {
+        // どの入力も決着がつく
+        assert_eq!(
+            Ok(0),
+            check_compiler(&Generator::default(), 500, 7, DEFAULT_FUEL)
+        );
+    }
+
+    #[test]
+    fn pinpoint_divergence() {
+        let c = parse_com("X := 1; Y := X + 2; while 1 <= Y do Y := Y - 1; Z := Y").unwrap();
+        let state = State::init();
+
+        // add を sub に取り違えたコンパイラ
+        let mut code = c.compile();
+        code.code[4] = Instr::Sub;
+        let e = compare(&c, &code, &state, 1000).unwrap_err();
+        assert_eq!(5, e.pc);
+        assert_eq!(
+            "`X := 1; Y := X + 2; while 1 <= Y do Y := Y - 1; Z := Y` from {}: \
+             expected {X ↦ 1, Y ↦ 0, Z ↦ 0}, got {X ↦ 1, Y ↦ -1, Z ↦ -1}\n\
+             first divergence at 5: store 1",
+            e.to_string()
+        );
+
+        // 被演算子を積み忘れるコンパイラはパニックせず、スタックが空になった命令を報告する
+        let mut code = c.compile();
+        code.code[3] = Instr::Add;
+        let e = compare(&c, &code, &state, 1000).unwrap_err();
+        assert_eq!(Err(VmError::StackUnderflow), e.actual);
+        assert_eq!(3, e.pc);
+
+        // ループを抜ける飛び先を誤り、Z := Y を飛ばしてしまうコンパイラ
+        let mut code = c.compile();
+        let Instr::Jz(end) = code.code[9] else {
+            panic!("{}", code)
+        };
+        code.code[9] = Instr::Jz(end + 2);
+        let e = compare(&c, &code, &state, 1000).unwrap_err();
+        assert_eq!(
+            Ok(State::from(&[("X", 1.into()), ("Y", 0.into())])),
+            e.actual
+        );
+        assert_eq!(9, e.pc);
+
+        // else 節の代わりに then 節へ飛び込んでしまうコンパイラ
+        // 食い違う代入ではなく、飛び先を誤った jz を報告する
+        let c = parse_com("if X = 0 then Y := 1 else skip; Z := Y").unwrap();
+        let state = State::from(&[("X", 1.into()), ("Y", 5.into())]);
+        let mut code = c.compile();
+        let Instr::Jz(_) = code.code[3] else {
+            panic!("{}", code)
+        };
+        code.code[3] = Instr::Jz(4);
+        let e = compare(&c, &code, &state, 1000).unwrap_err();
+        assert_eq!(3, e.pc);
+        assert!(e.to_string().ends_with("first divergence at 3: jz 4"));
+    }
+}
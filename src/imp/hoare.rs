@@ -0,0 +1,376 @@
+//! Hoare 論理による部分正当性の証明の検査
+//!
+//! ```text
+//!                                              {A} c_0 {C}  {C} c_1 {B}
+//! {A} skip {A}    {B[a/X]} X := a {B}         --------------------------
+//!                                                  {A} c_0; c_1 {B}
+//!
+//! {A ∧ b} c_0 {B}  {A ∧ ¬b} c_1 {B}                {A ∧ b} c {A}
+//! ----------------------------------    ----------------------------------
+//!   {A} if b then c_0 else c_1 {B}       {A} while b do c {A ∧ ¬b}
+//!
+//! ⊨ (A ⇒ A')  {A'} c {B'}  ⊨ (B' ⇒ B)
+//! -----------------------------------
+//!             {A} c {B}
+//! ```
+//!
+//! 帰結規則の前提 `⊨ (A ⇒ A')` は表明の妥当性なので、構造だけでは確かめられません。
+//! [`HoareProof::check`] は、これらを証明責務として返します。
+
+use std::fmt;
+
+use crate::{
+    imp::{assn::Assn, Aexp, Bexp, Com},
+    VarName,
+};
+
+/// 部分正当性の主張 `{A} c {B}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triple {
+    /// 事前条件
+    pub pre: Assn,
+    /// コマンド
+    pub com: Com,
+    /// 事後条件
+    pub post: Assn,
+}
+
+impl fmt::Display for Triple {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{{}}} {} {{{}}}", self.pre, self.com, self.post)
+    }
+}
+
+/// Hoare 論理の規則
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoareRule {
+    /// `{A} skip {A}`
+    Skip,
+    /// `{B[a/X]} X := a {B}`
+    Assign,
+    /// `{A} c_0; c_1 {B}`
+    Seq,
+    /// `{A} if b then c_0 else c_1 {B}`
+    If,
+    /// `{A} while b do c {A ∧ ¬b}`
+    While,
+    /// 事前条件を強め、事後条件を弱める
+    Consequence,
+}
+
+impl fmt::Display for HoareRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HoareRule::Skip => "skip",
+            HoareRule::Assign => "assign",
+            HoareRule::Seq => "seq",
+            HoareRule::If => "if",
+            HoareRule::While => "while",
+            HoareRule::Consequence => "consequence",
+        })
+    }
+}
+
+/// Hoare 論理の証明木
+#[derive(Debug, Clone, PartialEq)]
+pub struct HoareProof {
+    /// 最後に適用した規則
+    pub rule: HoareRule,
+    /// 前提の証明木
+    pub premises: Vec<HoareProof>,
+    /// 結論
+    pub conclusion: Triple,
+}
+
+impl HoareProof {
+    /// `{A} skip {A}`
+    pub fn skip(a: Assn) -> HoareProof {
+        HoareProof::axiom(HoareRule::Skip, a.clone(), Com::Skip, a)
+    }
+
+    /// `{B[a/X]} X := a {B}`
+    pub fn assign(x: &str, a: Aexp, post: Assn) -> HoareProof {
+        let x = VarName::from(x);
+        let pre = post.subst(&x, &a);
+        HoareProof::axiom(HoareRule::Assign, pre, Com::Subst(x, a), post)
+    }
+
+    /// 前提 `{A} c_0 {C}` と `{C} c_1 {B}` から `{A} c_0; c_1 {B}` を導きます。
+    pub fn seq(first: HoareProof, second: HoareProof) -> HoareProof {
+        let conclusion = Triple {
+            pre: first.conclusion.pre.clone(),
+            com: Com::Seq(
+                Box::new(first.conclusion.com.clone()),
+                Box::new(second.conclusion.com.clone()),
+            ),
+            post: second.conclusion.post.clone(),
+        };
+        HoareProof {
+            rule: HoareRule::Seq,
+            premises: vec![first, second],
+            conclusion,
+        }
+    }
+
+    /// 前提 `{A ∧ b} c_0 {B}` と `{A ∧ ¬b} c_1 {B}` から `{A} if b then c_0 else c_1 {B}` を導きます。
+    pub fn cond(pre: Assn, b: Bexp, then: HoareProof, otherwise: HoareProof) -> HoareProof {
+        let conclusion = Triple {
+            pre,
+            com: Com::If(
+                b,
+                Box::new(then.conclusion.com.clone()),
+                Box::new(otherwise.conclusion.com.clone()),
+            ),
+            post: then.conclusion.post.clone(),
+        };
+        HoareProof {
+            rule: HoareRule::If,
+            premises: vec![then, otherwise],
+            conclusion,
+        }
+    }
+
+    /// 前提 `{A ∧ b} c {A}` から `{A} while b do c {A ∧ ¬b}` を導きます。
+    pub fn while_loop(b: Bexp, body: HoareProof) -> HoareProof {
+        let invariant = body.conclusion.post.clone();
+        let conclusion = Triple {
+            pre: invariant.clone(),
+            post: Assn::and(invariant, Assn::not(b.clone().into())),
+            com: Com::While(b, Box::new(body.conclusion.com.clone())),
+        };
+        HoareProof {
+            rule: HoareRule::While,
+            premises: vec![body],
+            conclusion,
+        }
+    }
+
+    /// 前提 `{A'} c {B'}` から `{A} c {B}` を導きます。
+    pub fn consequence(pre: Assn, proof: HoareProof, post: Assn) -> HoareProof {
+        let conclusion = Triple {
+            pre,
+            com: proof.conclusion.com.clone(),
+            post,
+        };
+        HoareProof {
+            rule: HoareRule::Consequence,
+            premises: vec![proof],
+            conclusion,
+        }
+    }
+
+    fn axiom(rule: HoareRule, pre: Assn, com: Com, post: Assn) -> HoareProof {
+        HoareProof {
+            rule,
+            premises: vec![],
+            conclusion: Triple { pre, com, post },
+        }
+    }
+
+    /// 各節点で、規則の形と前提・結論が合っているかを確かめます。
+    ///
+    /// すべて合っていれば、帰結規則の前提のうち妥当性を確かめる必要がある含意
+    /// （証明責務）を前順に並べて返します。
+    /// 規則に沿わない節点があれば、最初に見つかったものを返します。
+    pub fn check(&self) -> Result<Vec<Assn>, &HoareProof> {
+        let mut obligations = Vec::new();
+        self.check_into(&mut obligations)?;
+        Ok(obligations)
+    }
+
+    fn check_into(&self, obligations: &mut Vec<Assn>) -> Result<(), &HoareProof> {
+        if !self.is_valid_step() {
+            return Err(self);
+        }
+        if self.rule == HoareRule::Consequence {
+            let (outer, inner) = (&self.conclusion, &self.premises[0].conclusion);
+            for (a, b) in [(&outer.pre, &inner.pre), (&inner.post, &outer.post)] {
+                if a != b {
+                    obligations.push(Assn::implies(a.clone(), b.clone()));
+                }
+            }
+        }
+        self.premises
+            .iter()
+            .try_for_each(|p| p.check_into(obligations))
+    }
+
+    /// 前提 `i` の結論を返します。
+    fn premise(&self, i: usize) -> Option<&Triple> {
+        self.premises.get(i).map(|p| &p.conclusion)
+    }
+
+    /// この節点だけについて、規則の適用が正しいかを返します。
+    fn is_valid_step(&self) -> bool {
+        let Triple { pre, com, post } = &self.conclusion;
+        let arity = match self.rule {
+            HoareRule::Skip | HoareRule::Assign => 0,
+            HoareRule::While | HoareRule::Consequence => 1,
+            HoareRule::Seq | HoareRule::If => 2,
+        };
+        if self.premises.len() != arity {
+            return false;
+        }
+        match (self.rule, com) {
+            (HoareRule::Skip, Com::Skip) => pre == post,
+            (HoareRule::Assign, Com::Subst(x, a)) => *pre == post.subst(x, a),
+            (HoareRule::Seq, Com::Seq(c_0, c_1)) => {
+                let (Some(first), Some(second)) = (self.premise(0), self.premise(1)) else {
+                    return false;
+                };
+                first.pre == *pre
+                    && first.com == **c_0
+                    && first.post == second.pre
+                    && second.com == **c_1
+                    && second.post == *post
+            }
+            (HoareRule::If, Com::If(b, c_0, c_1)) => {
+                let (Some(then), Some(otherwise)) = (self.premise(0), self.premise(1)) else {
+                    return false;
+                };
+                let b = Assn::from(b.clone());
+                then.pre == Assn::and(pre.clone(), b.clone())
+                    && then.com == **c_0
+                    && then.post == *post
+                    && otherwise.pre == Assn::and(pre.clone(), Assn::not(b))
+                    && otherwise.com == **c_1
+                    && otherwise.post == *post
+            }
+            (HoareRule::While, Com::While(b, c)) => {
+                let Some(body) = self.premise(0) else {
+                    return false;
+                };
+                let b = Assn::from(b.clone());
+                body.pre == Assn::and(pre.clone(), b.clone())
+                    && body.com == **c
+                    && body.post == *pre
+                    && *post == Assn::and(pre.clone(), Assn::not(b))
+            }
+            (HoareRule::Consequence, _) => self.premise(0).is_some_and(|inner| inner.com == *com),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::imp::{
+        assn::{Aexpv, Assn},
+        hoare::{HoareProof, HoareRule, Triple},
+        parse_aexp, parse_bexp, parse_com,
+    };
+
+    fn assn(b: &str) -> Assn {
+        parse_bexp(b).unwrap().into()
+    }
+
+    fn loc_eq(x: &str, i: &str) -> Assn {
+        Assn::Eq(Aexpv::Loc(x.into()), Aexpv::var(i))
+    }
+
+    /// `{X = m ∧ Y = n} T := X; X := Y; Y := T {X = n ∧ Y = m}`
+    fn swap() -> HoareProof {
+        let post = Assn::and(loc_eq("X", "n"), loc_eq("Y", "m"));
+        let third = HoareProof::assign("Y", parse_aexp("T").unwrap(), post);
+        let second =
+            HoareProof::assign("X", parse_aexp("Y").unwrap(), third.conclusion.pre.clone());
+        let first =
+            HoareProof::assign("T", parse_aexp("X").unwrap(), second.conclusion.pre.clone());
+        let proof = HoareProof::seq(first, HoareProof::seq(second, third));
+        HoareProof::consequence(
+            Assn::and(loc_eq("X", "m"), loc_eq("Y", "n")),
+            proof,
+            Assn::and(loc_eq("X", "n"), loc_eq("Y", "m")),
+        )
+    }
+
+    #[test]
+    fn check_swap() {
+        let proof = swap();
+        assert_eq!(
+            parse_com("T := X; X := Y; Y := T").unwrap(),
+            proof.conclusion.com
+        );
+        // 最弱の事前条件 Y = n ∧ X = m への強め
+        let obligations = proof.check().unwrap();
+        assert_eq!(1, obligations.len());
+        assert_eq!("X = m ∧ Y = n ⇒ Y = n ∧ X = m", obligations[0].to_string());
+    }
+
+    #[test]
+    fn check_while() {
+        // {0 <= X} while 1 <= X do X := X - 1 {0 <= X ∧ ¬1 <= X}
+        let b = parse_bexp("1 <= X").unwrap();
+        let invariant = assn("0 <= X");
+        let body = HoareProof::assign("X", parse_aexp("X - 1").unwrap(), invariant.clone());
+        let body = HoareProof::consequence(
+            Assn::and(invariant.clone(), b.clone().into()),
+            body,
+            invariant.clone(),
+        );
+        let proof = HoareProof::while_loop(b, body);
+        assert_eq!(
+            "{0 <= X} while 1 <= X do X := X - 1 {0 <= X ∧ ¬1 <= X}",
+            proof.conclusion.to_string()
+        );
+        assert_eq!(
+            vec!["0 <= X ∧ 1 <= X ⇒ 0 <= X - 1".to_string()],
+            proof
+                .check()
+                .unwrap()
+                .iter()
+                .map(Assn::to_string)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn check_if() {
+        // {true} if X <= Y then Z := Y else Z := X {X <= Z ∧ Y <= Z}
+        let b = parse_bexp("X <= Y").unwrap();
+        let post = assn("X <= Z and Y <= Z");
+        let branch = |a: &str, cond: Assn| {
+            HoareProof::consequence(
+                cond,
+                HoareProof::assign("Z", parse_aexp(a).unwrap(), post.clone()),
+                post.clone(),
+            )
+        };
+        let proof = HoareProof::cond(
+            assn("true"),
+            b.clone(),
+            branch("Y", Assn::and(assn("true"), b.clone().into())),
+            branch("X", Assn::and(assn("true"), Assn::not(b.into()))),
+        );
+        assert_eq!(2, proof.check().unwrap().len());
+    }
+
+    #[test]
+    fn check_rejects_invalid_step() {
+        let mut proof = swap();
+
+        // 代入の事前条件を書き換えると、その節点が誤りになる
+        proof.premises[0].conclusion.pre = assn("true");
+        proof.premises[0].premises[0].conclusion.pre = assn("true");
+        let expected = proof.premises[0].premises[0].clone();
+        assert_eq!(Err(&expected), proof.check());
+
+        // 逐次実行の中間の表明が合わない
+        let mut proof = swap();
+        proof.premises[0].premises[1].conclusion.pre = assn("false");
+        let expected = proof.premises[0].clone();
+        assert_eq!(Err(&expected), proof.check());
+
+        // 規則とコマンドの形が合わない
+        let wrong = HoareProof {
+            rule: HoareRule::Skip,
+            premises: vec![],
+            conclusion: Triple {
+                pre: assn("true"),
+                com: parse_com("X := 1").unwrap(),
+                post: assn("true"),
+            },
+        };
+        assert_eq!(Err(&wrong), wrong.check());
+    }
+}
@@ -0,0 +1,523 @@
+//! 区間領域による抽象解釈
+//!
+//! 各変数の値の範囲を区間 `[lo, hi]` で近似して、コマンドの各地点で変数がとりうる値を求めます。
+//! `while` の先頭では拡大（widening）で不動点に到達させ、その後の縮小（narrowing）で精度を取り戻します。
+//!
+//! ```text
+//! X := 0: {}
+//! while X <= 9: {X ↦ [0, 10]}
+//! X := X + 1: {X ↦ [0, 9]}
+//! exit: {X ↦ [10, 10]}
+//! ```
+//!
+//! 演算の結果が `i32` の範囲を超えうるときはオーバーフローとして報告します。
+//! 報告がなければ、初期状態が与えた範囲にあるどの実行でもオーバーフローは起きません。
+//! オーバーフローした実行はそこで止まるので、区間は常に `i32` の範囲に収めます。
+//!
+//! また、各 `while` について本体を抽象的に展開していき、
+//! 条件が成り立ちえなくなるまでの回数をループの反復回数の上限として求めます。
+
+use std::{cmp, fmt};
+
+use crate::{
+    imp::{Aexp, Bexp, BexpImpl, Com},
+    State,
+};
+
+/// ループの反復回数の上限を求めるときに展開する回数の上限
+pub const MAX_UNROLL: u64 = 1 << 10;
+
+/// 縮小を繰り返す回数の上限
+const NARROWING_STEPS: usize = 8;
+
+/// 空でない整数の区間 `[lo, hi]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    /// 下限
+    pub lo: i32,
+    /// 上限
+    pub hi: i32,
+}
+
+impl Interval {
+    /// 区間 `[lo, hi]` を作ります。
+    pub fn new(lo: i32, hi: i32) -> Interval {
+        assert!(lo <= hi, "empty interval [{}, {}]", lo, hi);
+        Interval { lo, hi }
+    }
+
+    /// `i32` のすべての値からなる区間
+    pub const TOP: Interval = Interval {
+        lo: i32::MIN,
+        hi: i32::MAX,
+    };
+
+    /// 1 点からなる区間 `[n, n]`
+    pub fn constant(n: i32) -> Interval {
+        Interval { lo: n, hi: n }
+    }
+
+    /// `n` を含むかどうか
+    pub fn contains(&self, n: i32) -> bool {
+        self.lo <= n && n <= self.hi
+    }
+
+    fn join(self, other: Interval) -> Interval {
+        Interval {
+            lo: cmp::min(self.lo, other.lo),
+            hi: cmp::max(self.hi, other.hi),
+        }
+    }
+
+    fn meet(self, other: Interval) -> Option<Interval> {
+        let lo = cmp::max(self.lo, other.lo);
+        let hi = cmp::min(self.hi, other.hi);
+        (lo <= hi).then_some(Interval { lo, hi })
+    }
+
+    /// 拡大: 広がった側の端を `i32` の端まで広げます。
+    fn widen(self, new: Interval) -> Interval {
+        Interval {
+            lo: if new.lo < self.lo { i32::MIN } else { self.lo },
+            hi: if new.hi > self.hi { i32::MAX } else { self.hi },
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+/// 抽象状態。`None` は到達しないこと（⊥）を表します。
+type Abs = Option<State<Interval>>;
+
+fn join(s: Abs, t: Abs) -> Abs {
+    match (s, t) {
+        (None, t) => t,
+        (s, None) => s,
+        (Some(s), Some(t)) => Some(join_state(s, &t)),
+    }
+}
+
+/// 一方にしかない変数は、もう一方の経路で読むと実行が止まるので、その区間をそのまま残します。
+fn join_state(s: State<Interval>, t: &State<Interval>) -> State<Interval> {
+    merge(s, t, Interval::join)
+}
+
+fn merge(
+    mut s: State<Interval>,
+    t: &State<Interval>,
+    op: fn(Interval, Interval) -> Interval,
+) -> State<Interval> {
+    for (x, j) in &t.0 {
+        let Some(j) = j else { continue };
+        let i = match s.get(x) {
+            Some(i) => op(*i, *j),
+            None => *j,
+        };
+        s = s.update_variable(x, i);
+    }
+    s
+}
+
+/// 区間解析の結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    /// 各コマンド（`;` を除く）を実行する直前の抽象状態。コマンドの出現順に並べます。
+    /// `while` では、ループの先頭で毎回成り立つ抽象状態です。
+    pub points: Vec<(Com, Option<State<Interval>>)>,
+    /// 実行を終えた後の抽象状態
+    pub exit: Option<State<Interval>>,
+    /// オーバーフローしうる演算
+    pub overflows: Vec<Aexp>,
+    /// 各 `while` の条件と反復回数の上限。上限が求まらなければ `None` です。
+    pub loop_bounds: Vec<(Bexp, Option<u64>)>,
+}
+
+impl Analysis {
+    /// オーバーフローが起きないことを示せたかどうか
+    pub fn is_overflow_free(&self) -> bool {
+        self.overflows.is_empty()
+    }
+}
+
+/// 地点ごとに `while X <= 9: {X ↦ [0, 10]}` の形で出力します。到達しない地点は `⊥` です。
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_abs(f: &mut fmt::Formatter<'_>, s: &Abs) -> fmt::Result {
+            match s {
+                Some(s) => write!(f, "{}", s),
+                None => f.write_str("⊥"),
+            }
+        }
+
+        for (c, s) in &self.points {
+            match c {
+                Com::Skip => f.write_str("skip")?,
+                Com::Subst(x, a) => write!(f, "{} := {}", x, a)?,
+                Com::If(b, ..) => write!(f, "if {}", b)?,
+                Com::While(b, _) => write!(f, "while {}", b)?,
+                Com::Seq(..) => unreachable!(),
+            }
+            f.write_str(": ")?;
+            write_abs(f, s)?;
+            writeln!(f)?;
+        }
+        f.write_str("exit: ")?;
+        write_abs(f, &self.exit)
+    }
+}
+
+/// 初期状態の変数が `init` の範囲にあるとして `c` を解析します。
+/// `init` にない変数は、代入される前に読むと実行が止まるものとして扱います。
+pub fn analyze(c: &Com, init: &State<Interval>) -> Analysis {
+    let n = count(c);
+    let mut analyzer = Analyzer {
+        record: true,
+        points: vec![None; n],
+        overflows: vec![],
+        bounds: vec![None; n],
+    };
+    let exit = analyzer.exec(c, 0, Some(init.clone()));
+
+    let mut coms = vec![];
+    collect(c, &mut coms);
+    let loop_bounds = coms
+        .iter()
+        .zip(&analyzer.bounds)
+        .filter_map(|(c, bound)| match c {
+            Com::While(b, _) => Some((b.clone(), *bound)),
+            _ => None,
+        })
+        .collect();
+    Analysis {
+        points: coms.into_iter().zip(analyzer.points).collect(),
+        exit,
+        overflows: analyzer.overflows,
+        loop_bounds,
+    }
+}
+
+/// `c` に含まれる `;` 以外のコマンドの個数
+fn count(c: &Com) -> usize {
+    match c {
+        Com::Skip | Com::Subst(..) => 1,
+        Com::Seq(c_0, c_1) => count(c_0) + count(c_1),
+        Com::If(_, c_0, c_1) => 1 + count(c_0) + count(c_1),
+        Com::While(_, c) => 1 + count(c),
+    }
+}
+
+/// `c` に含まれる `;` 以外のコマンドを出現順に集めます。
+fn collect(c: &Com, coms: &mut Vec<Com>) {
+    match c {
+        Com::Skip | Com::Subst(..) => coms.push(c.clone()),
+        Com::Seq(c_0, c_1) => {
+            collect(c_0, coms);
+            collect(c_1, coms);
+        }
+        Com::If(_, c_0, c_1) => {
+            coms.push(c.clone());
+            collect(c_0, coms);
+            collect(c_1, coms);
+        }
+        Com::While(_, body) => {
+            coms.push(c.clone());
+            collect(body, coms);
+        }
+    }
+}
+
+struct Analyzer {
+    /// 結果を記録するかどうか。不動点を求める途中の計算は記録しません。
+    record: bool,
+    points: Vec<Abs>,
+    overflows: Vec<Aexp>,
+    bounds: Vec<Option<u64>>,
+}
+
+impl Analyzer {
+    /// `id` 番から番号を振った `c` を抽象状態 `s` から実行します。
+    fn exec(&mut self, c: &Com, id: usize, s: Abs) -> Abs {
+        if let Com::Seq(c_0, c_1) = c {
+            let s = self.exec(c_0, id, s);
+            return self.exec(c_1, id + count(c_0), s);
+        }
+        if self.record && !matches!(c, Com::While(..)) {
+            self.points[id] = join(self.points[id].take(), s.clone());
+        }
+
+        let s = s?;
+        match c {
+            Com::Skip => Some(s),
+            Com::Subst(x, a) => {
+                let i = self.eval(a, &s)?;
+                Some(s.update_variable(x, i))
+            }
+            Com::If(b, c_0, c_1) => {
+                let s_0 = self.assume(b, true, &s);
+                let s_1 = self.assume(b, false, &s);
+                let s_0 = self.exec(c_0, id + 1, s_0);
+                let s_1 = self.exec(c_1, id + 1 + count(c_0), s_1);
+                join(s_0, s_1)
+            }
+            Com::While(b, body) => {
+                let record = std::mem::replace(&mut self.record, false);
+
+                // 拡大で不動点に到達させる
+                let mut head = s.clone();
+                loop {
+                    let next = self.iterate(b, body, id, &s, &head);
+                    let widened = merge(head.clone(), &next, Interval::widen);
+                    if widened == head {
+                        break;
+                    }
+                    head = widened;
+                }
+                // 縮小で精度を取り戻す
+                for _ in 0..NARROWING_STEPS {
+                    let next = self.iterate(b, body, id, &s, &head);
+                    if next == head {
+                        break;
+                    }
+                    head = next;
+                }
+
+                let bound = record.then(|| self.bound(b, body, id, &s)).flatten();
+                self.record = record;
+                if record {
+                    self.points[id] = join(self.points[id].take(), Some(head.clone()));
+                    self.bounds[id] = bound;
+                }
+
+                let s_body = self.assume(b, true, &head);
+                self.exec(body, id + 1, s_body);
+                self.assume(b, false, &head)
+            }
+            Com::Seq(..) => unreachable!(),
+        }
+    }
+
+    /// ループの先頭の抽象状態 `head` から本体を 1 回実行し、ループに入る前の抽象状態 `entry` と合わせます。
+    fn iterate(
+        &mut self,
+        b: &Bexp,
+        body: &Com,
+        id: usize,
+        entry: &State<Interval>,
+        head: &State<Interval>,
+    ) -> State<Interval> {
+        let s = self.assume(b, true, head);
+        match self.exec(body, id + 1, s) {
+            Some(s) => join_state(entry.clone(), &s),
+            None => entry.clone(),
+        }
+    }
+
+    /// `entry` から本体を展開していき、条件が成り立ちえなくなるまでの回数を返します。
+    fn bound(&mut self, b: &Bexp, body: &Com, id: usize, entry: &State<Interval>) -> Option<u64> {
+        let mut s = entry.clone();
+        for k in 0..MAX_UNROLL {
+            let Some(t) = self.assume(b, true, &s) else {
+                return Some(k);
+            };
+            let Some(next) = self.exec(body, id + 1, Some(t)) else {
+                return Some(k + 1);
+            };
+            if next == s {
+                // 抽象状態が変わらないなら、いつまでも条件が成り立ちうる
+                return None;
+            }
+            s = next;
+        }
+        None
+    }
+
+    /// 式の値の区間を返します。読む変数が代入されていなければ `None` を返します。
+    fn eval(&mut self, a: &Aexp, s: &State<Interval>) -> Option<Interval> {
+        let (a_0, a_1) = match a {
+            Aexp::N(n) => return Some(Interval::constant(n.0)),
+            Aexp::Loc(x) => return *s.get(x),
+            Aexp::Add(a_0, a_1) | Aexp::Sub(a_0, a_1) | Aexp::Mul(a_0, a_1) => (a_0, a_1),
+        };
+        let i = self.eval(a_0, s)?;
+        let j = self.eval(a_1, s)?;
+        let (i_lo, i_hi, j_lo, j_hi) = (i.lo as i64, i.hi as i64, j.lo as i64, j.hi as i64);
+        let (lo, hi) = match a {
+            Aexp::Add(..) => (i_lo + j_lo, i_hi + j_hi),
+            Aexp::Sub(..) => (i_lo - j_hi, i_hi - j_lo),
+            _ => {
+                let products = [i_lo * j_lo, i_lo * j_hi, i_hi * j_lo, i_hi * j_hi];
+                (*products.iter().min()?, *products.iter().max()?)
+            }
+        };
+
+        let overflow = lo < i32::MIN as i64 || hi > i32::MAX as i64;
+        if overflow && self.record && !self.overflows.contains(a) {
+            self.overflows.push(a.clone());
+        }
+        // オーバーフローした実行は止まるので、i32 の範囲に収まる値だけを残す
+        let lo = cmp::max(lo, i32::MIN as i64);
+        let hi = cmp::min(hi, i32::MAX as i64);
+        (lo <= hi).then(|| Interval::new(lo as i32, hi as i32))
+    }
+
+    /// 条件 `b` の値が `truth` になるように抽象状態を絞り込みます。
+    fn assume(&mut self, b: &Bexp, truth: bool, s: &State<Interval>) -> Abs {
+        self.assume_impl(&b.bexp, truth, s)
+    }
+
+    fn assume_impl(&mut self, b: &BexpImpl, truth: bool, s: &State<Interval>) -> Abs {
+        match b {
+            BexpImpl::T(t) => (bool::from(*t) == truth).then(|| s.clone()),
+            BexpImpl::Not(b) => self.assume_impl(b, !truth, s),
+            BexpImpl::And(b_0, b_1) | BexpImpl::Or(b_0, b_1) => {
+                // and が真、or が偽のときは両辺がその値になる
+                let both = matches!(b, BexpImpl::And(..)) == truth;
+                let first = self.assume_impl(b_0, truth, s);
+                if both {
+                    return self.assume_impl(b_1, truth, &first?);
+                }
+                // 短絡評価されるか、左辺が逆の値で右辺が truth になる
+                let rest = match self.assume_impl(b_0, !truth, s) {
+                    Some(s) => self.assume_impl(b_1, truth, &s),
+                    None => None,
+                };
+                join(first, rest)
+            }
+            BexpImpl::Le(a_0, a_1) => {
+                let i_0 = self.eval(a_0, s)?;
+                let i_1 = self.eval(a_1, s)?;
+                // l + d <= r の形にする
+                let (l, r, i_l, i_r, d) = if truth {
+                    (a_0, a_1, i_0, i_1, 0)
+                } else {
+                    (a_1, a_0, i_1, i_0, 1)
+                };
+                if i_l.lo as i64 + d > i_r.hi as i64 {
+                    return None;
+                }
+                let mut s = s.clone();
+                if let Aexp::Loc(x) = l {
+                    let hi = cmp::min(i_l.hi as i64, i_r.hi as i64 - d) as i32;
+                    s = s.update_variable(x, Interval::new(i_l.lo, hi));
+                }
+                if let Aexp::Loc(y) = r {
+                    let lo = cmp::max(i_r.lo as i64, i_l.lo as i64 + d) as i32;
+                    s = s.update_variable(y, Interval::new(lo, i_r.hi));
+                }
+                Some(s)
+            }
+            BexpImpl::Eq(a_0, a_1) => {
+                let i_0 = self.eval(a_0, s)?;
+                let i_1 = self.eval(a_1, s)?;
+                let mut s = s.clone();
+                if truth {
+                    let i = i_0.meet(i_1)?;
+                    for a in [a_0, a_1] {
+                        if let Aexp::Loc(x) = a {
+                            s = s.update_variable(x, i);
+                        }
+                    }
+                } else {
+                    if i_0.lo == i_0.hi && i_0 == i_1 {
+                        return None;
+                    }
+                    // 一方が定数で他方の端にあれば、その端を除く
+                    for (a, i, j) in [(a_0, i_0, i_1), (a_1, i_1, i_0)] {
+                        if let (Aexp::Loc(x), true) = (a, j.lo == j.hi) {
+                            if i.lo == j.lo {
+                                s = s.update_variable(x, Interval::new(i.lo + 1, i.hi));
+                            } else if i.hi == j.lo {
+                                s = s.update_variable(x, Interval::new(i.lo, i.hi - 1));
+                            }
+                        }
+                    }
+                }
+                Some(s)
+            }
+            BexpImpl::Dummy => panic!(), // 短絡評価のテスト用
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            interval::{analyze, Interval},
+            parse_aexp, parse_com,
+        },
+        Execute, State,
+    };
+
+    #[test]
+    fn counting_loop() {
+        let c = parse_com("X := 0; while X <= 9 do X := X + 1").unwrap();
+        let analysis = analyze(&c, &State::init());
+        assert_eq!(
+            "X := 0: {}\n\
+             while X <= 9: {X ↦ [0, 10]}\n\
+             X := X + 1: {X ↦ [0, 9]}\n\
+             exit: {X ↦ [10, 10]}",
+            analysis.to_string()
+        );
+        assert!(analysis.is_overflow_free());
+        assert_eq!(Some(10), analysis.loop_bounds[0].1);
+    }
+
+    #[test]
+    fn overflow() {
+        let c = parse_com("Y := X * X * X").unwrap();
+        let small = State::from(&[("X", Interval::new(-1000, 1000))]);
+        assert!(analyze(&c, &small).is_overflow_free());
+        let large = State::from(&[("X", Interval::new(0, 2000))]);
+        assert_eq!(
+            vec![parse_aexp("X * X * X").unwrap()],
+            analyze(&c, &large).overflows
+        );
+
+        // N が最大値なら I + 1 がオーバーフローする
+        let c = parse_com("I := 0; while I <= N do I := I + 1").unwrap();
+        let analysis = analyze(&c, &State::from(&[("N", Interval::new(0, 100))]));
+        assert!(analysis.is_overflow_free());
+        assert_eq!(Some(101), analysis.loop_bounds[0].1);
+        let analysis = analyze(&c, &State::from(&[("N", Interval::new(0, i32::MAX))]));
+        assert_eq!(vec![parse_aexp("I + 1").unwrap()], analysis.overflows);
+    }
+
+    #[test]
+    fn loop_bounds() {
+        let c = parse_com(
+            "while 1 <= X do { Y := 0; while Y <= 2 do Y := Y + 1; X := X - 1 }; \
+             while 1 <= Z do Z := Z + 1",
+        )
+        .unwrap();
+        let init = State::from(&[("X", Interval::new(0, 5)), ("Z", Interval::new(0, 1))]);
+        let analysis = analyze(&c, &init);
+        let bounds: Vec<_> = analysis.loop_bounds.iter().map(|(_, n)| *n).collect();
+        assert_eq!(vec![Some(5), Some(3), None], bounds);
+        assert_eq!(vec![parse_aexp("Z + 1").unwrap()], analysis.overflows);
+    }
+
+    #[test]
+    fn sound_for_concrete_runs() {
+        let c = parse_com(
+            "Y := 0; if X <= 2 then Z := 0 - X else Z := X * 2; \
+             while Y <= Z and 1 <= Z do { Y := Y + 3; if Y = 6 then Z := Z - 1 else skip }",
+        )
+        .unwrap();
+        let analysis = analyze(&c, &State::from(&[("X", Interval::new(-5, 5))]));
+        let exit = analysis.exit.unwrap();
+        for x in -5..=5 {
+            let (_, state) = c.execute(State::from(&[("X", x.into())]));
+            for var in ["X", "Y", "Z"] {
+                let n = state.get(&var.into()).unwrap();
+                let i = exit.get(&var.into()).unwrap();
+                assert!(i.contains(n.0), "{} = {} ∉ {}", var, n, i);
+            }
+        }
+    }
+}
@@ -0,0 +1,972 @@
+//! IMP の具象構文の構文解析器
+//!
+//! 演算子の結合の強さは次のとおりです（上ほど強い）。
+//!
+//! ```text
+//! "*"
+//! "+" "-"            （左結合）
+//! "=" "<="
+//! "not"
+//! "and"              （左結合）
+//! "or"               （左結合）
+//! ";"                （右結合）
+//! ```
+//!
+//! 算術式・ブール式は `( ... )` で、コマンドは `{ ... }` でまとめることができます。
+//! `if` の各分岐と `while` の本体は `;` を含まないコマンドなので、
+//! 逐次実行を書くときは `{ ... }` で囲みます。
+
+use std::fmt;
+
+use crate::{
+    imp::{Aexp, Bexp, Com},
+    Number, VarName,
+};
+
+/// ソースコード上の位置（1 始まりの行と列）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// ソースコード上の範囲 `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.start)
+    }
+}
+
+/// 構文解析のエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// 予期しない字句
+    UnexpectedToken {
+        /// 見つかった字句
+        found: String,
+        /// その位置で受理できた字句
+        expected: Vec<String>,
+        span: Span,
+    },
+    /// 字句として認識できない文字
+    UnexpectedCharacter { found: char, span: Span },
+    /// `{` に対応する `}` がないまま入力が終わった
+    UnterminatedBlock {
+        /// 閉じられていない `{` の位置
+        open: Span,
+        /// 入力の終わりの位置
+        span: Span,
+    },
+    /// `Number` の範囲に収まらない整数リテラル
+    InvalidNumber { literal: String, span: Span },
+    /// 予約語を `VarName` として使っている
+    ReservedWord { word: String, span: Span },
+}
+
+impl ParseError {
+    /// エラーの位置を返します。
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedCharacter { span, .. }
+            | ParseError::UnterminatedBlock { span, .. }
+            | ParseError::InvalidNumber { span, .. }
+            | ParseError::ReservedWord { span, .. } => *span,
+        }
+    }
+
+    /// エラーの位置で受理できた字句の一覧を返します。
+    pub fn expected(&self) -> &[String] {
+        match self {
+            ParseError::UnexpectedToken { expected, .. } => expected,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.span())?;
+        match self {
+            ParseError::UnexpectedToken {
+                found, expected, ..
+            } => match expected.as_slice() {
+                [] => write!(f, "unexpected {}", found),
+                [expected] => write!(f, "expected {}, found {}", expected, found),
+                expected => write!(
+                    f,
+                    "expected one of {}, found {}",
+                    expected.join(", "),
+                    found
+                ),
+            },
+            ParseError::UnexpectedCharacter { found, .. } => {
+                write!(f, "unexpected character `{}`", found)
+            }
+            ParseError::UnterminatedBlock { open, .. } => {
+                write!(f, "unterminated block: `{{` at {} is never closed", open)
+            }
+            ParseError::InvalidNumber { literal, .. } => {
+                write!(f, "integer literal `{}` is out of range", literal)
+            }
+            ParseError::ReservedWord { word, .. } => {
+                write!(f, "`{}` is a reserved word and cannot be a variable", word)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// `word` が変数名として字句解析できる識別子なら `true` を返します。
+///
+/// 識別子は英字か `_` で始まり、英数字と `_` が続く、予約語でない語です。
+pub fn is_identifier(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && keyword(word).is_none()
+}
+
+/// コマンドを構文解析します。
+pub fn parse_com(src: &str) -> Result<Com, ParseError> {
+    let mut parser = Parser::new(src)?;
+    let com = parser.com()?;
+    parser.expect_eof()?;
+    Ok(com)
+}
+
+/// 算術式を構文解析します。
+pub fn parse_aexp(src: &str) -> Result<Aexp, ParseError> {
+    let mut parser = Parser::new(src)?;
+    let aexp = parser.aexp()?;
+    parser.expect_eof()?;
+    Ok(aexp)
+}
+
+/// ブール式を構文解析します。
+pub fn parse_bexp(src: &str) -> Result<Bexp, ParseError> {
+    let mut parser = Parser::new(src)?;
+    let bexp = parser.bexp()?;
+    parser.expect_eof()?;
+    Ok(bexp)
+}
+
+/// 字句
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Ident(String),
+    Skip,
+    If,
+    Then,
+    Else,
+    While,
+    Do,
+    True,
+    False,
+    Not,
+    And,
+    Or,
+    Assign,
+    Semi,
+    Plus,
+    Minus,
+    Star,
+    Eq,
+    Le,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Eof,
+}
+
+impl Token {
+    /// 予約語ならその綴りを返します。
+    fn keyword(&self) -> Option<&'static str> {
+        Some(match self {
+            Token::Skip => "skip",
+            Token::If => "if",
+            Token::Then => "then",
+            Token::Else => "else",
+            Token::While => "while",
+            Token::Do => "do",
+            Token::True => "true",
+            Token::False => "false",
+            Token::Not => "not",
+            Token::And => "and",
+            Token::Or => "or",
+            _ => return None,
+        })
+    }
+
+    /// 「expected one of ...」に並べるための字句の種類の名前
+    fn kind(&self) -> String {
+        match self {
+            Token::Number(_) => "number".to_string(),
+            Token::Ident(_) => "variable".to_string(),
+            Token::Eof => "end of input".to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(word) = self.keyword() {
+            return write!(f, "`{}`", word);
+        }
+        match self {
+            Token::Number(n) => write!(f, "number `{}`", n),
+            Token::Ident(x) => write!(f, "variable `{}`", x),
+            Token::Assign => write!(f, "`:=`"),
+            Token::Semi => write!(f, "`;`"),
+            Token::Plus => write!(f, "`+`"),
+            Token::Minus => write!(f, "`-`"),
+            Token::Star => write!(f, "`*`"),
+            Token::Eq => write!(f, "`=`"),
+            Token::Le => write!(f, "`<=`"),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::LBrace => write!(f, "`{{`"),
+            Token::RBrace => write!(f, "`}}`"),
+            Token::Eof => write!(f, "end of input"),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn keyword(word: &str) -> Option<Token> {
+    Some(match word {
+        "skip" => Token::Skip,
+        "if" => Token::If,
+        "then" => Token::Then,
+        "else" => Token::Else,
+        "while" => Token::While,
+        "do" => Token::Do,
+        "true" => Token::True,
+        "false" => Token::False,
+        "not" => Token::Not,
+        "and" => Token::And,
+        "or" => Token::Or,
+        _ => return None,
+    })
+}
+
+/// 位置を数えながら文字を読む字句解析器
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    pos: Position,
+}
+
+impl Lexer<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.chars.clone().nth(1)
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else if c.is_some() {
+            self.pos.column += 1;
+        }
+        c
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.peek() {
+            if !pred(c) {
+                break;
+            }
+            s.push(c);
+            self.advance();
+        }
+        s
+    }
+}
+
+/// ソースコードを字句の列に分解します。
+fn tokenize(src: &str) -> Result<Vec<(Token, Span)>, ParseError> {
+    let mut lexer = Lexer {
+        chars: src.chars().peekable(),
+        pos: Position { line: 1, column: 1 },
+    };
+    let mut tokens = Vec::new();
+
+    while let Some(c) = lexer.peek() {
+        let start = lexer.pos;
+        let token = match c {
+            _ if c.is_whitespace() => {
+                lexer.advance();
+                continue;
+            }
+            '0'..='9' => Token::Number(lexer.take_while(|d| d.is_ascii_digit())),
+            // 被演算子の直後でなければ、数字の直前の `-` は整数リテラルの符号
+            '-' if lexer.peek_second().is_some_and(|d| d.is_ascii_digit())
+                && !matches!(
+                    tokens.last(),
+                    Some((Token::Number(_) | Token::Ident(_) | Token::RParen, _))
+                ) =>
+            {
+                lexer.advance();
+                Token::Number(format!("-{}", lexer.take_while(|d| d.is_ascii_digit())))
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let word = lexer.take_while(|d| d.is_alphanumeric() || d == '_');
+                keyword(&word).unwrap_or(Token::Ident(word))
+            }
+            ':' | '<' => {
+                lexer.advance();
+                if lexer.peek() != Some('=') {
+                    let (token, expected) = if c == ':' {
+                        ("`:`", Token::Assign)
+                    } else {
+                        ("`<`", Token::Le)
+                    };
+                    return Err(ParseError::UnexpectedToken {
+                        found: token.to_string(),
+                        expected: vec![expected.kind()],
+                        span: Span {
+                            start,
+                            end: lexer.pos,
+                        },
+                    });
+                }
+                lexer.advance();
+                if c == ':' {
+                    Token::Assign
+                } else {
+                    Token::Le
+                }
+            }
+            _ => {
+                let token = match c {
+                    ';' => Token::Semi,
+                    '+' => Token::Plus,
+                    '-' => Token::Minus,
+                    '*' => Token::Star,
+                    '=' => Token::Eq,
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    '{' => Token::LBrace,
+                    '}' => Token::RBrace,
+                    _ => {
+                        lexer.advance();
+                        return Err(ParseError::UnexpectedCharacter {
+                            found: c,
+                            span: Span {
+                                start,
+                                end: lexer.pos,
+                            },
+                        });
+                    }
+                };
+                lexer.advance();
+                token
+            }
+        };
+        tokens.push((
+            token,
+            Span {
+                start,
+                end: lexer.pos,
+            },
+        ));
+    }
+    tokens.push((
+        Token::Eof,
+        Span {
+            start: lexer.pos,
+            end: lexer.pos,
+        },
+    ));
+    Ok(tokens)
+}
+
+/// 再帰下降構文解析器
+struct Parser {
+    tokens: Vec<(Token, Span)>,
+    pos: usize,
+    /// `expected_at` の位置で試した字句の種類
+    expected: Vec<String>,
+    expected_at: usize,
+}
+
+impl Parser {
+    fn new(src: &str) -> Result<Parser, ParseError> {
+        Ok(Parser {
+            tokens: tokenize(src)?,
+            pos: 0,
+            expected: Vec::new(),
+            expected_at: 0,
+        })
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn peek_nth(&self, n: usize) -> &Token {
+        &self.tokens[(self.pos + n).min(self.tokens.len() - 1)].0
+    }
+
+    fn span(&self) -> Span {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// 現在の位置で `kind` を受理しようとしたことを記録します。
+    fn expecting(&mut self, kind: impl Into<String>) {
+        if self.expected_at != self.pos {
+            self.expected.clear();
+            self.expected_at = self.pos;
+        }
+        let kind = kind.into();
+        if !self.expected.contains(&kind) {
+            self.expected.push(kind);
+        }
+    }
+
+    /// 次の字句が `token` なら読み進めて `true` を返します。
+    fn eat(&mut self, token: Token) -> bool {
+        self.expecting(token.kind());
+        if *self.peek() == token {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 現在の字句を予期しない字句として報告します。
+    fn unexpected(&self) -> ParseError {
+        let expected = if self.expected_at == self.pos {
+            self.expected.clone()
+        } else {
+            Vec::new()
+        };
+        ParseError::UnexpectedToken {
+            found: self.peek().to_string(),
+            expected,
+            span: self.span(),
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn expect_eof(&mut self) -> Result<(), ParseError> {
+        self.expect(Token::Eof)
+    }
+
+    /// 予約語が変数の位置に現れていれば `ReservedWord` を返します。
+    fn reserved_word(&self) -> Option<ParseError> {
+        let word = self.peek().keyword()?;
+        match self.peek_nth(1) {
+            Token::Assign | Token::Plus | Token::Minus | Token::Star | Token::Eq | Token::Le => {
+                Some(ParseError::ReservedWord {
+                    word: word.to_string(),
+                    span: self.span(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// ```text
+    /// com ::= simple (";" com)?
+    /// ```
+    fn com(&mut self) -> Result<Com, ParseError> {
+        let first = self.simple_com()?;
+        if self.eat(Token::Semi) {
+            let rest = self.com()?;
+            Ok(Com::Seq(Box::new(first), Box::new(rest)))
+        } else {
+            Ok(first)
+        }
+    }
+
+    /// ```text
+    /// simple ::= "skip" | VarName ":=" aexp
+    ///          | "if" bexp "then" simple "else" simple
+    ///          | "while" bexp "do" simple
+    ///          | "{" com "}"
+    /// ```
+    fn simple_com(&mut self) -> Result<Com, ParseError> {
+        if let Some(e) = self.reserved_word() {
+            return Err(e);
+        }
+        for kind in ["`skip`", "variable", "`if`", "`while`", "`{`"] {
+            self.expecting(kind);
+        }
+        let open = self.span();
+        match self.peek().clone() {
+            Token::Skip => {
+                self.next();
+                Ok(Com::Skip)
+            }
+            Token::Ident(name) => {
+                self.next();
+                self.expect(Token::Assign)?;
+                let a = self.aexp()?;
+                Ok(Com::Subst(VarName::from(name), a))
+            }
+            Token::If => {
+                self.next();
+                let b = self.bexp()?;
+                self.expect(Token::Then)?;
+                let c_0 = self.simple_com()?;
+                self.expect(Token::Else)?;
+                let c_1 = self.simple_com()?;
+                Ok(Com::If(b, Box::new(c_0), Box::new(c_1)))
+            }
+            Token::While => {
+                self.next();
+                let b = self.bexp()?;
+                self.expect(Token::Do)?;
+                let c = self.simple_com()?;
+                Ok(Com::While(b, Box::new(c)))
+            }
+            Token::LBrace => {
+                self.next();
+                let c = self.com()?;
+                if self.eat(Token::RBrace) {
+                    Ok(c)
+                } else if *self.peek() == Token::Eof {
+                    Err(ParseError::UnterminatedBlock {
+                        open,
+                        span: self.span(),
+                    })
+                } else {
+                    Err(self.unexpected())
+                }
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// ```text
+    /// aexp ::= term (("+" | "-") term)*
+    /// ```
+    fn aexp(&mut self) -> Result<Aexp, ParseError> {
+        let mut left = self.term()?;
+        loop {
+            if self.eat(Token::Plus) {
+                let right = self.term()?;
+                left = Aexp::Add(Box::new(left), Box::new(right));
+            } else if self.eat(Token::Minus) {
+                let right = self.term()?;
+                left = Aexp::Sub(Box::new(left), Box::new(right));
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    /// ```text
+    /// term ::= factor ("*" factor)*
+    /// ```
+    fn term(&mut self) -> Result<Aexp, ParseError> {
+        let mut left = self.factor()?;
+        while self.eat(Token::Star) {
+            let right = self.factor()?;
+            left = Aexp::Mul(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    /// ```text
+    /// factor ::= Number | "-" Number | VarName | "(" aexp ")"
+    /// ```
+    fn factor(&mut self) -> Result<Aexp, ParseError> {
+        if let Some(e) = self.reserved_word() {
+            return Err(e);
+        }
+        for kind in ["number", "`-`", "variable", "`(`"] {
+            self.expecting(kind);
+        }
+        let start = self.span();
+        match self.peek().clone() {
+            Token::Number(digits) => {
+                self.next();
+                number(&digits, start)
+            }
+            Token::Minus => {
+                self.next();
+                self.expecting("number");
+                let Token::Number(digits) = self.peek().clone() else {
+                    return Err(self.unexpected());
+                };
+                let end = self.span();
+                self.next();
+                number(
+                    &format!("-{}", digits),
+                    Span {
+                        start: start.start,
+                        end: end.end,
+                    },
+                )
+            }
+            Token::Ident(name) => {
+                self.next();
+                Ok(Aexp::Loc(VarName::from(name)))
+            }
+            Token::LParen => {
+                self.next();
+                let a = self.aexp()?;
+                self.expect(Token::RParen)?;
+                Ok(a)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// ```text
+    /// bexp ::= conj ("or" conj)*
+    /// ```
+    fn bexp(&mut self) -> Result<Bexp, ParseError> {
+        let mut left = self.conj()?;
+        while self.eat(Token::Or) {
+            let right = self.conj()?;
+            left = Bexp::or(left, right);
+        }
+        Ok(left)
+    }
+
+    /// ```text
+    /// conj ::= neg ("and" neg)*
+    /// ```
+    fn conj(&mut self) -> Result<Bexp, ParseError> {
+        let mut left = self.neg()?;
+        while self.eat(Token::And) {
+            let right = self.neg()?;
+            left = Bexp::and(left, right);
+        }
+        Ok(left)
+    }
+
+    /// ```text
+    /// neg ::= "not" neg | batom
+    /// ```
+    fn neg(&mut self) -> Result<Bexp, ParseError> {
+        if self.eat(Token::Not) {
+            Ok(Bexp::not(self.neg()?))
+        } else {
+            self.batom()
+        }
+    }
+
+    /// ```text
+    /// batom ::= "true" | "false" | "(" bexp ")" | aexp ("=" | "<=") aexp
+    /// ```
+    ///
+    /// `(` で始まる場合はブール式と算術式のどちらの括弧か決まらないので、
+    /// まずブール式として読み、失敗したら比較式として読み直します。
+    /// どちらも失敗したときは、より先まで読めたほうのエラーを返します。
+    fn batom(&mut self) -> Result<Bexp, ParseError> {
+        if self.eat(Token::True) {
+            return Ok(Bexp::truth(true));
+        }
+        if self.eat(Token::False) {
+            return Ok(Bexp::truth(false));
+        }
+        if *self.peek() == Token::LParen {
+            let start = self.pos;
+            self.next();
+            let first_error = match self.bexp() {
+                Ok(b) if self.eat(Token::RParen) => return Ok(b),
+                Ok(_) => self.unexpected(),
+                Err(e) => e,
+            };
+            self.pos = start;
+            self.expected.clear();
+            return self.comparison().map_err(|e| {
+                if first_error.span().start > e.span().start {
+                    first_error
+                } else {
+                    e
+                }
+            });
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Bexp, ParseError> {
+        let left = self.aexp()?;
+        if self.eat(Token::Eq) {
+            Ok(Bexp::eq(left, self.aexp()?))
+        } else if self.eat(Token::Le) {
+            Ok(Bexp::le(left, self.aexp()?))
+        } else {
+            Err(self.unexpected())
+        }
+    }
+}
+
+fn number(literal: &str, span: Span) -> Result<Aexp, ParseError> {
+    literal
+        .parse::<i32>()
+        .map(|n| Aexp::N(Number::from(n)))
+        .map_err(|_| ParseError::InvalidNumber {
+            literal: literal.to_string(),
+            span,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            parser::{parse_aexp, parse_bexp, parse_com, ParseError, Position, Span},
+            Aexp, Bexp, Com,
+        },
+        Execute, Number, State,
+    };
+
+    fn n(n: i32) -> Box<Aexp> {
+        Box::new(Aexp::N(n.into()))
+    }
+
+    fn x(name: &str) -> Box<Aexp> {
+        Box::new(Aexp::Loc(name.into()))
+    }
+
+    fn span(line: usize, column: usize, width: usize) -> Span {
+        Span {
+            start: Position { line, column },
+            end: Position {
+                line,
+                column: column + width,
+            },
+        }
+    }
+
+    #[test]
+    fn parse_arithmetic_precedence() {
+        // 1 + 2 * 3 ≡ 1 + (2 * 3)
+        assert_eq!(
+            Ok(Aexp::Add(n(1), Box::new(Aexp::Mul(n(2), n(3))))),
+            parse_aexp("1 + 2 * 3"),
+        );
+
+        // 1 - 2 + X ≡ (1 - 2) + X
+        assert_eq!(
+            Ok(Aexp::Add(Box::new(Aexp::Sub(n(1), n(2))), x("X"))),
+            parse_aexp("1 - 2 + X"),
+        );
+
+        // (1 + 2) * -3
+        assert_eq!(
+            Ok(Aexp::Mul(Box::new(Aexp::Add(n(1), n(2))), n(-3))),
+            parse_aexp("(1 + 2) * -3"),
+        );
+
+        assert_eq!(Ok(Aexp::N(i32::MIN.into())), parse_aexp("-2147483648"));
+    }
+
+    #[test]
+    fn parse_boolean_precedence() {
+        // not X = 0 and true or false ≡ ((not (X = 0)) and true) or false
+        assert_eq!(
+            Ok(Bexp::or(
+                Bexp::and(Bexp::not(Bexp::eq(*x("X"), *n(0))), Bexp::truth(true),),
+                Bexp::truth(false),
+            )),
+            parse_bexp("not X = 0 and true or false"),
+        );
+
+        // 括弧はブール式にも算術式にも使える
+        assert_eq!(
+            Ok(Bexp::and(
+                Bexp::le(Aexp::Add(x("X"), n(1)), *n(3)),
+                Bexp::not(Bexp::or(Bexp::truth(false), Bexp::eq(*x("Y"), *x("X")))),
+            )),
+            parse_bexp("(X + 1) <= 3 and not (false or (Y) = X)"),
+        );
+    }
+
+    #[test]
+    fn parse_commands() {
+        // X := 1 ; Y := 2 ; skip ≡ X := 1 ; (Y := 2 ; skip)
+        assert_eq!(
+            Ok(Com::Seq(
+                Box::new(Com::Subst("X".into(), *n(1))),
+                Box::new(Com::Seq(
+                    Box::new(Com::Subst("Y".into(), *n(2))),
+                    Box::new(Com::Skip),
+                )),
+            )),
+            parse_com("X := 1; Y := 2; skip"),
+        );
+
+        // while の本体は `;` で終わる
+        assert_eq!(
+            Ok(Com::Seq(
+                Box::new(Com::While(
+                    Bexp::le(*x("X"), *n(3)),
+                    Box::new(Com::Subst("X".into(), Aexp::Add(x("X"), n(1)))),
+                )),
+                Box::new(Com::Skip),
+            )),
+            parse_com("while X <= 3 do X := X + 1; skip"),
+        );
+
+        assert_eq!(
+            Ok(Com::If(
+                Bexp::truth(true),
+                Box::new(Com::Seq(Box::new(Com::Skip), Box::new(Com::Skip))),
+                Box::new(Com::Skip),
+            )),
+            parse_com("if true then { skip; skip } else skip"),
+        );
+    }
+
+    #[test]
+    fn execute_parsed_program() {
+        // σ := { (X, 0) }
+        // ⟨while X <= 3 do X := X + 1, σ⟩ →* ⟨(), σ[4/X]⟩
+        let com = parse_com("while X <= 3 do X := X + 1").unwrap();
+        let (None, state) = com.execute(State::from(&[("X", 0.into())])) else {
+            panic!()
+        };
+        assert_eq!(&Some(Number(4)), state.get(&"X".into()));
+    }
+
+    #[test]
+    fn unexpected_token() {
+        assert_eq!(
+            Err(ParseError::UnexpectedToken {
+                found: "end of input".to_string(),
+                expected: vec![
+                    "number".to_string(),
+                    "`-`".to_string(),
+                    "variable".to_string(),
+                    "`(`".to_string(),
+                ],
+                span: span(2, 6, 0),
+            }),
+            parse_com("X := 1;\nY := "),
+        );
+
+        let e = parse_com("X := 1 Y := 2").unwrap_err();
+        assert_eq!(span(1, 8, 1), e.span());
+        assert_eq!(
+            "1:8: expected one of `*`, `+`, `-`, `;`, end of input, found variable `Y`",
+            e.to_string(),
+        );
+
+        let e = parse_bexp("X").unwrap_err();
+        assert_eq!(&["`*`", "`+`", "`-`", "`=`", "`<=`"], e.expected());
+
+        assert_eq!(
+            Err(ParseError::UnexpectedCharacter {
+                found: '#',
+                span: span(1, 6, 1),
+            }),
+            parse_com("X := #"),
+        );
+    }
+
+    #[test]
+    fn unterminated_block() {
+        assert_eq!(
+            Err(ParseError::UnterminatedBlock {
+                open: span(1, 15, 1),
+                span: span(3, 1, 0),
+            }),
+            parse_com("while true do {\n  skip\n"),
+        );
+    }
+
+    #[test]
+    fn invalid_number() {
+        assert_eq!(
+            Err(ParseError::InvalidNumber {
+                literal: "2147483648".to_string(),
+                span: span(1, 1, 10),
+            }),
+            parse_aexp("2147483648"),
+        );
+        assert_eq!(
+            Err(ParseError::InvalidNumber {
+                literal: "-2147483649".to_string(),
+                span: span(1, 5, 11),
+            }),
+            parse_aexp("1 + -2147483649"),
+        );
+    }
+
+    #[test]
+    fn signed_literal() {
+        // 予約語の直後の `-1` も符号付きの整数
+        assert_eq!(
+            Ok(Com::Seq(
+                Box::new(Com::If(
+                    Bexp::le(*n(-1), *x("X")),
+                    Box::new(Com::Skip),
+                    Box::new(Com::Skip),
+                )),
+                Box::new(Com::While(
+                    Bexp::le(*n(-1), *x("X")),
+                    Box::new(Com::Subst("X".into(), Aexp::Sub(x("X"), n(1)))),
+                )),
+            )),
+            parse_com("if -1 <= X then skip else skip; while -1 <= X do X := X - 1"),
+        );
+        // 被演算子の直後の `-` は減算
+        assert_eq!(Ok(Aexp::Sub(x("X"), n(1))), parse_aexp("X -1"));
+        assert_eq!(Ok(Aexp::Sub(n(2), n(1))), parse_aexp("(2)-1"));
+        assert_eq!(Ok(Aexp::Sub(x("X"), n(-1))), parse_aexp("X - -1"));
+        assert_eq!(Ok(Aexp::Mul(n(2), n(-3))), parse_aexp("2*-3"));
+    }
+
+    #[test]
+    fn reserved_word() {
+        assert_eq!(
+            Err(ParseError::ReservedWord {
+                word: "do".to_string(),
+                span: span(1, 8, 2),
+            }),
+            parse_com("skip ; do := 1"),
+        );
+        assert_eq!(
+            Err(ParseError::ReservedWord {
+                word: "then".to_string(),
+                span: span(1, 6, 4),
+            }),
+            parse_com("X := then + 1"),
+        );
+    }
+}
@@ -0,0 +1,396 @@
+//! IMP の抽象構文木を具象構文に戻す清書器
+//!
+//! 括弧は [`parser`](crate::imp::parser) の結合規則のもとで必要な箇所にだけ付けるので、
+//! 出力を構文解析すると元と等しい抽象構文木が得られます。
+//! ただし変数名はそのまま出力するので、これが成り立つのはどの変数名も
+//! [`is_identifier`](crate::imp::parser::is_identifier) を満たすときだけです。
+//! 予約語や識別子でない変数名を含む構文木の出力は構文解析できません。
+//!
+//! `Display` は 1 行で出力し、`{:#}` を指定すると [`Printer::default`] で
+//! `if`/`while` の本体を字下げした複数行で出力します。
+
+use std::fmt::{self, Write};
+
+use crate::imp::{Aexp, Bexp, BexpImpl, Com};
+
+/// 算術式の二項演算子
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl ArithOp {
+    /// 結合の強さ
+    fn precedence(self) -> u8 {
+        match self {
+            ArithOp::Add | ArithOp::Sub => 1,
+            ArithOp::Mul => 2,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            ArithOp::Add => "+",
+            ArithOp::Sub => "-",
+            ArithOp::Mul => "*",
+        }
+    }
+}
+
+/// 算術式の形
+pub(crate) enum ArithForm<'a, A> {
+    /// 整数や変数
+    Atom(&'a dyn fmt::Display),
+    /// 二項演算
+    Binary(ArithOp, &'a A, &'a A),
+}
+
+/// 算術式として清書できる構文木
+///
+/// [`Aexp`] と表明の算術式 [`Aexpv`](crate::imp::assn::Aexpv) で括弧の付け方を共有します。
+pub(crate) trait ArithSyntax: Sized {
+    fn form(&self) -> ArithForm<'_, Self>;
+}
+
+impl ArithSyntax for Aexp {
+    fn form(&self) -> ArithForm<'_, Self> {
+        match self {
+            Aexp::N(n) => ArithForm::Atom(n),
+            Aexp::Loc(x) => ArithForm::Atom(x),
+            Aexp::Add(left, right) => ArithForm::Binary(ArithOp::Add, left, right),
+            Aexp::Sub(left, right) => ArithForm::Binary(ArithOp::Sub, left, right),
+            Aexp::Mul(left, right) => ArithForm::Binary(ArithOp::Mul, left, right),
+        }
+    }
+}
+
+/// 結合の強さが `min` 未満なら括弧で囲んで出力します。
+pub(crate) fn write_aexp<A: ArithSyntax>(f: &mut impl Write, a: &A, min: u8) -> fmt::Result {
+    match a.form() {
+        ArithForm::Atom(atom) => write!(f, "{}", atom),
+        ArithForm::Binary(op, left, right) => {
+            let prec = op.precedence();
+            let paren = prec < min;
+            if paren {
+                f.write_char('(')?;
+            }
+            // 左結合なので右側は一段強く結合するものだけを裸で置ける
+            write_aexp(f, left, prec)?;
+            write!(f, " {} ", op.symbol())?;
+            write_aexp(f, right, prec + 1)?;
+            if paren {
+                f.write_char(')')?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// ブール式の結合の強さ
+fn bexp_precedence(b: &BexpImpl) -> u8 {
+    match b {
+        BexpImpl::Or(..) => 1,
+        BexpImpl::And(..) => 2,
+        BexpImpl::Not(_) => 3,
+        BexpImpl::T(_) | BexpImpl::Eq(..) | BexpImpl::Le(..) | BexpImpl::Dummy => 4,
+    }
+}
+
+fn write_bexp(f: &mut impl Write, b: &BexpImpl, min: u8) -> fmt::Result {
+    let paren = bexp_precedence(b) < min;
+    if paren {
+        f.write_char('(')?;
+    }
+    match b {
+        BexpImpl::T(t) => write!(f, "{}", t)?,
+        BexpImpl::Eq(left, right) | BexpImpl::Le(left, right) => {
+            write_aexp(f, left, 0)?;
+            f.write_str(if let BexpImpl::Eq(..) = b {
+                " = "
+            } else {
+                " <= "
+            })?;
+            write_aexp(f, right, 0)?;
+        }
+        BexpImpl::Not(b) => {
+            f.write_str("not ")?;
+            write_bexp(f, b, 3)?;
+        }
+        BexpImpl::And(left, right) | BexpImpl::Or(left, right) => {
+            let (op, prec) = match b {
+                BexpImpl::And(..) => ("and", 2),
+                _ => ("or", 1),
+            };
+            write_bexp(f, left, prec)?;
+            write!(f, " {} ", op)?;
+            write_bexp(f, right, prec + 1)?;
+        }
+        BexpImpl::Dummy => f.write_char('⊥')?,
+    }
+    if paren {
+        f.write_char(')')?;
+    }
+    Ok(())
+}
+
+/// 1 行で出力します。
+fn write_com_inline(f: &mut impl Write, c: &Com) -> fmt::Result {
+    match c {
+        Com::Skip => f.write_str("skip"),
+        Com::Subst(x, a) => {
+            write!(f, "{} := ", x)?;
+            write_aexp(f, a, 0)
+        }
+        Com::Seq(c_0, c_1) => {
+            write_simple_inline(f, c_0)?;
+            f.write_str("; ")?;
+            write_com_inline(f, c_1)
+        }
+        Com::If(b, c_0, c_1) => {
+            write!(f, "if {} then ", b)?;
+            write_simple_inline(f, c_0)?;
+            f.write_str(" else ")?;
+            write_simple_inline(f, c_1)
+        }
+        Com::While(b, c) => {
+            write!(f, "while {} do ", b)?;
+            write_simple_inline(f, c)
+        }
+    }
+}
+
+/// `;` を含まないコマンドとして 1 行で出力します。
+fn write_simple_inline(f: &mut impl Write, c: &Com) -> fmt::Result {
+    if let Com::Seq(..) = c {
+        f.write_str("{ ")?;
+        write_com_inline(f, c)?;
+        f.write_str(" }")
+    } else {
+        write_com_inline(f, c)
+    }
+}
+
+/// 複数行の清書器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Printer {
+    /// 1 段の字下げの幅
+    indent: usize,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Printer { indent: 4 }
+    }
+}
+
+impl Printer {
+    /// 1 段の字下げの幅を `indent` 文字とする清書器を生成します。
+    pub fn new(indent: usize) -> Printer {
+        Printer { indent }
+    }
+
+    /// コマンドを清書した文字列を返します。
+    pub fn print(&self, c: &Com) -> String {
+        let mut out = String::new();
+        self.write_com(&mut out, c, 0)
+            .expect("writing to a String never fails");
+        out
+    }
+
+    fn newline(&self, f: &mut impl Write, level: usize) -> fmt::Result {
+        writeln!(f)?;
+        write!(f, "{:1$}", "", self.indent * level)
+    }
+
+    fn write_com(&self, f: &mut impl Write, c: &Com, level: usize) -> fmt::Result {
+        match c {
+            Com::Seq(c_0, c_1) => {
+                self.write_simple(f, c_0, level)?;
+                f.write_char(';')?;
+                self.newline(f, level)?;
+                self.write_com(f, c_1, level)
+            }
+            Com::If(b, c_0, c_1) => {
+                write!(f, "if {} then", b)?;
+                if self.write_body(f, c_0, level)? {
+                    f.write_char(' ')?;
+                } else {
+                    self.newline(f, level)?;
+                }
+                f.write_str("else")?;
+                self.write_body(f, c_1, level)?;
+                Ok(())
+            }
+            Com::While(b, c) => {
+                write!(f, "while {} do", b)?;
+                self.write_body(f, c, level)?;
+                Ok(())
+            }
+            Com::Skip | Com::Subst(..) => write_com_inline(f, c),
+        }
+    }
+
+    fn write_simple(&self, f: &mut impl Write, c: &Com, level: usize) -> fmt::Result {
+        if let Com::Seq(..) = c {
+            self.write_block(f, c, level)
+        } else {
+            self.write_com(f, c, level)
+        }
+    }
+
+    fn write_block(&self, f: &mut impl Write, c: &Com, level: usize) -> fmt::Result {
+        f.write_char('{')?;
+        self.newline(f, level + 1)?;
+        self.write_com(f, c, level + 1)?;
+        self.newline(f, level)?;
+        f.write_char('}')
+    }
+
+    /// `if`/`while` の本体を出力します。ブロックとして出力したら `true` を返します。
+    fn write_body(&self, f: &mut impl Write, c: &Com, level: usize) -> Result<bool, fmt::Error> {
+        if let Com::Seq(..) = c {
+            f.write_char(' ')?;
+            self.write_block(f, c, level)?;
+            Ok(true)
+        } else {
+            self.newline(f, level + 1)?;
+            self.write_com(f, c, level + 1)?;
+            Ok(false)
+        }
+    }
+}
+
+impl fmt::Display for Aexp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_aexp(f, self, 0)
+    }
+}
+
+impl fmt::Display for Bexp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_bexp(f, &self.bexp, 0)
+    }
+}
+
+impl fmt::Display for Com {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            Printer::default().write_com(f, self, 0)
+        } else {
+            write_com_inline(f, self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::imp::{
+        parse_aexp, parse_bexp, parse_com, parser::is_identifier, printer::Printer, Aexp, Bexp, Com,
+    };
+
+    fn n(n: i32) -> Box<Aexp> {
+        Box::new(Aexp::N(n.into()))
+    }
+
+    fn x(name: &str) -> Box<Aexp> {
+        Box::new(Aexp::Loc(name.into()))
+    }
+
+    #[test]
+    fn print_minimal_parentheses() {
+        // (1 + 2) * 3
+        assert_eq!(
+            "(1 + 2) * 3",
+            Aexp::Mul(Box::new(Aexp::Add(n(1), n(2))), n(3)).to_string(),
+        );
+        // 1 + 2 * 3
+        assert_eq!(
+            "1 + 2 * 3",
+            Aexp::Add(n(1), Box::new(Aexp::Mul(n(2), n(3)))).to_string(),
+        );
+        // X - (Y - -1)
+        assert_eq!(
+            "X - (Y - -1)",
+            Aexp::Sub(x("X"), Box::new(Aexp::Sub(x("Y"), n(-1)))).to_string(),
+        );
+        assert_eq!(
+            "not (true and X <= 1) or false",
+            Bexp::or(
+                Bexp::not(Bexp::and(Bexp::truth(true), Bexp::le(*x("X"), *n(1)))),
+                Bexp::truth(false),
+            )
+            .to_string(),
+        );
+    }
+
+    #[test]
+    fn print_indented() {
+        let c = parse_com(
+            "X := 0; while X <= 3 do { if X = 1 then Y := X else { skip; Y := 0 }; X := X + 1 }",
+        )
+        .unwrap();
+        assert_eq!(
+            "X := 0;\n\
+             while X <= 3 do {\n  \
+               if X = 1 then\n    \
+                 Y := X\n  \
+               else {\n    \
+                 skip;\n    \
+                 Y := 0\n  \
+               };\n  \
+               X := X + 1\n\
+             }",
+            Printer::new(2).print(&c),
+        );
+        assert_eq!(Printer::default().print(&c), format!("{:#}", c));
+    }
+
+    #[test]
+    fn round_trip() {
+        for src in [
+            "1 - (2 - 3) - 4 * (5 * 6)",
+            "(X + -2147483648) * (Y - Z) - 0",
+        ] {
+            let a = parse_aexp(src).unwrap();
+            assert_eq!(Ok(a.clone()), parse_aexp(&a.to_string()));
+        }
+
+        for src in [
+            "not not (X = 1 or Y <= 2) and (true or false) or not false",
+            "(X + 1) * 2 <= 3 and (true and (false or true))",
+        ] {
+            let b = parse_bexp(src).unwrap();
+            assert_eq!(Ok(b.clone()), parse_bexp(&b.to_string()));
+        }
+
+        let seq = |c_0: Com, c_1: Com| Com::Seq(Box::new(c_0), Box::new(c_1));
+        for c in [
+            // (skip ; skip) ; (skip ; skip)
+            seq(seq(Com::Skip, Com::Skip), seq(Com::Skip, Com::Skip)),
+            parse_com("if true then while false do { skip; skip } else if false then skip else skip; X := 1")
+                .unwrap(),
+            parse_com("while not X = 0 do { while X <= 0 do X := X + 1; X := X - 1 }").unwrap(),
+        ] {
+            assert_eq!(Ok(c.clone()), parse_com(&c.to_string()));
+            assert_eq!(Ok(c.clone()), parse_com(&Printer::new(3).print(&c)));
+        }
+    }
+
+    #[test]
+    fn identifier_names_only() {
+        for name in ["X", "_0", "x_1", "ifX"] {
+            assert!(is_identifier(name), "{}", name);
+            let c = Com::Subst(name.into(), *x(name));
+            assert_eq!(Ok(c.clone()), parse_com(&c.to_string()));
+        }
+
+        // 予約語や識別子でない変数名は、出力しても構文解析できない
+        for name in ["if", "do", "", "1X", "X Y", "X-1"] {
+            assert!(!is_identifier(name), "{}", name);
+            let c = Com::Subst(name.into(), *n(0));
+            assert_ne!(Ok(c.clone()), parse_com(&c.to_string()), "{:?}", name);
+        }
+    }
+}
@@ -0,0 +1,528 @@
+//! ランダムテスト
+//!
+//! ランダムな IMP プログラムを生成し、2 つの意味論がどの入力でも同じ結果になるかを確かめます。
+//! 結果が食い違ったら、食い違いが残る範囲でプログラムをできるだけ小さくして報告します。
+//!
+//! ```text
+//! let gen = Generator::default();
+//! check_equivalence(&gen, 1000, 42, operational, denotational)?;
+//! ```
+
+use std::fmt;
+
+use crate::{
+    imp::{Aexp, Bexp, BexpImpl, Com},
+    State, VarName,
+};
+
+/// [`operational`] で与える燃料
+pub const DEFAULT_FUEL: u64 = 1 << 20;
+
+/// 擬似乱数生成器（SplitMix64）
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// シード `seed` から生成器を作ります。
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// 次の 64 ビットの乱数を返します。
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce5_e4b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// `0..n` の範囲の乱数を返します。
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// `lo..=hi` の範囲の乱数を返します。`hi < lo` なら `lo` を返します。
+    pub fn range(&mut self, lo: i32, hi: i32) -> i32 {
+        // i32 の全域でも溢れないよう、幅は i64 で計算する
+        let width = (i64::from(hi) - i64::from(lo)).max(0) as u64 + 1;
+        (i64::from(lo) + self.below(width) as i64) as i32
+    }
+
+    /// スライスの要素を 1 つ選びます。空なら `None` を返します。
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        Some(&items[self.below(items.len() as u64) as usize])
+    }
+}
+
+/// ランダムなプログラムと状態の生成器
+///
+/// `while` はどれも、本体で書き換えない専用の変数 `_k`（`k` は入れ子の深さ）で
+/// 回数を数えるので必ず停止します。
+///
+/// ```text
+/// _0 := n; while b and 1 <= _0 do { c; _0 := _0 - 1 }
+/// ```
+///
+/// `vars` が空なら、変数は読まずに整数リテラルを使い、代入の代わりに `skip` を生成します。
+#[derive(Debug, Clone)]
+pub struct Generator {
+    /// 使う変数
+    pub vars: Vec<VarName>,
+    /// 式やコマンドの木の深さの上限
+    pub max_depth: u32,
+    /// 整数リテラルと初期状態の値の絶対値の上限
+    pub max_literal: i32,
+    /// 1 つの `while` の繰り返し回数の上限
+    pub max_iterations: i32,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator {
+            vars: vec!["X".into(), "Y".into(), "Z".into()],
+            max_depth: 3,
+            max_literal: 5,
+            max_iterations: 3,
+        }
+    }
+}
+
+impl Generator {
+    /// ランダムな算術式を返します。
+    pub fn aexp(&self, rng: &mut Rng, depth: u32) -> Aexp {
+        if depth == 0 || rng.below(3) == 0 {
+            let var = match rng.below(2) {
+                0 => None,
+                _ => rng.choose(&self.vars),
+            };
+            return match var {
+                Some(x) => Aexp::Loc(x.clone()),
+                None => Aexp::N(rng.range(-self.max_literal, self.max_literal).into()),
+            };
+        }
+        let left = Box::new(self.aexp(rng, depth - 1));
+        let right = Box::new(self.aexp(rng, depth - 1));
+        match rng.below(3) {
+            0 => Aexp::Add(left, right),
+            1 => Aexp::Sub(left, right),
+            _ => Aexp::Mul(left, right),
+        }
+    }
+
+    /// ランダムなブール式を返します。
+    pub fn bexp(&self, rng: &mut Rng, depth: u32) -> Bexp {
+        if depth == 0 || rng.below(3) == 0 {
+            return match rng.below(5) {
+                0 => Bexp::truth(rng.below(2) == 0),
+                1 | 2 => Bexp::eq(self.aexp(rng, depth), self.aexp(rng, depth)),
+                _ => Bexp::le(self.aexp(rng, depth), self.aexp(rng, depth)),
+            };
+        }
+        match rng.below(3) {
+            0 => Bexp::not(self.bexp(rng, depth - 1)),
+            1 => Bexp::and(self.bexp(rng, depth - 1), self.bexp(rng, depth - 1)),
+            _ => Bexp::or(self.bexp(rng, depth - 1), self.bexp(rng, depth - 1)),
+        }
+    }
+
+    /// ランダムなコマンドを返します。
+    pub fn com(&self, rng: &mut Rng) -> Com {
+        self.com_at(rng, self.max_depth, 0)
+    }
+
+    fn com_at(&self, rng: &mut Rng, depth: u32, loops: u32) -> Com {
+        let choice = if depth == 0 {
+            rng.below(2)
+        } else {
+            rng.below(5)
+        };
+        match choice {
+            0 => Com::Skip,
+            1 => match rng.choose(&self.vars) {
+                Some(x) => Com::Subst(x.clone(), self.aexp(rng, depth.min(2))),
+                None => Com::Skip,
+            },
+            2 => Com::Seq(
+                Box::new(self.com_at(rng, depth - 1, loops)),
+                Box::new(self.com_at(rng, depth - 1, loops)),
+            ),
+            3 => Com::If(
+                self.bexp(rng, depth.min(2)),
+                Box::new(self.com_at(rng, depth - 1, loops)),
+                Box::new(self.com_at(rng, depth - 1, loops)),
+            ),
+            _ => {
+                let counter = VarName::from(format!("_{}", loops));
+                let decrement = Com::Subst(
+                    counter.clone(),
+                    Aexp::Sub(
+                        Box::new(Aexp::Loc(counter.clone())),
+                        Box::new(Aexp::N(1.into())),
+                    ),
+                );
+                let body = Com::Seq(
+                    Box::new(self.com_at(rng, depth - 1, loops + 1)),
+                    Box::new(decrement),
+                );
+                let cond = Bexp::and(
+                    self.bexp(rng, depth.min(2)),
+                    Bexp::le(Aexp::N(1.into()), Aexp::Loc(counter.clone())),
+                );
+                Com::Seq(
+                    Box::new(Com::Subst(
+                        counter,
+                        Aexp::N(rng.range(0, self.max_iterations).into()),
+                    )),
+                    Box::new(Com::While(cond, Box::new(body))),
+                )
+            }
+        }
+    }
+
+    /// すべての変数に値を持つランダムな状態を返します。
+    pub fn state(&self, rng: &mut Rng) -> State {
+        let mut state = State::init();
+        for var in &self.vars {
+            let value = rng.range(-self.max_literal, self.max_literal);
+            state = state.update_variable(var, value.into());
+        }
+        state
+    }
+}
+
+impl Aexp {
+    /// 自身より 1 段小さい算術式の候補を返します。
+    pub fn shrink(&self) -> Vec<Aexp> {
+        match self {
+            Aexp::N(n) if *n == 0 => vec![],
+            Aexp::N(_) => vec![Aexp::N(0.into())],
+            Aexp::Loc(_) => vec![Aexp::N(0.into())],
+            Aexp::Add(a0, a1) | Aexp::Sub(a0, a1) | Aexp::Mul(a0, a1) => {
+                let rebuild = |a0: Aexp, a1: Aexp| match self {
+                    Aexp::Add(..) => Aexp::Add(Box::new(a0), Box::new(a1)),
+                    Aexp::Sub(..) => Aexp::Sub(Box::new(a0), Box::new(a1)),
+                    _ => Aexp::Mul(Box::new(a0), Box::new(a1)),
+                };
+                let mut candidates = vec![(**a0).clone(), (**a1).clone()];
+                candidates.extend(a0.shrink().into_iter().map(|a| rebuild(a, (**a1).clone())));
+                candidates.extend(a1.shrink().into_iter().map(|a| rebuild((**a0).clone(), a)));
+                candidates
+            }
+        }
+    }
+}
+
+impl Bexp {
+    /// 自身より 1 段小さいブール式の候補を返します。
+    pub fn shrink(&self) -> Vec<Bexp> {
+        self.bexp
+            .shrink()
+            .into_iter()
+            .map(|bexp| Bexp { bexp })
+            .collect()
+    }
+}
+
+impl BexpImpl {
+    fn shrink(&self) -> Vec<BexpImpl> {
+        let constants = || vec![BexpImpl::T(true.into()), BexpImpl::T(false.into())];
+        match self {
+            BexpImpl::T(t) if bool::from(*t) => vec![],
+            BexpImpl::T(_) => vec![BexpImpl::T(true.into())],
+            BexpImpl::Eq(a0, a1) | BexpImpl::Le(a0, a1) => {
+                let rebuild = |a0: Aexp, a1: Aexp| match self {
+                    BexpImpl::Eq(..) => BexpImpl::Eq(a0, a1),
+                    _ => BexpImpl::Le(a0, a1),
+                };
+                let mut candidates = constants();
+                candidates.extend(a0.shrink().into_iter().map(|a| rebuild(a, a1.clone())));
+                candidates.extend(a1.shrink().into_iter().map(|a| rebuild(a0.clone(), a)));
+                candidates
+            }
+            BexpImpl::Not(b) => {
+                let mut candidates = constants();
+                candidates.push((**b).clone());
+                candidates.extend(b.shrink().into_iter().map(|b| BexpImpl::Not(Box::new(b))));
+                candidates
+            }
+            BexpImpl::And(b0, b1) | BexpImpl::Or(b0, b1) => {
+                let rebuild = |b0: BexpImpl, b1: BexpImpl| match self {
+                    BexpImpl::And(..) => BexpImpl::And(Box::new(b0), Box::new(b1)),
+                    _ => BexpImpl::Or(Box::new(b0), Box::new(b1)),
+                };
+                let mut candidates = constants();
+                candidates.extend([(**b0).clone(), (**b1).clone()]);
+                candidates.extend(b0.shrink().into_iter().map(|b| rebuild(b, (**b1).clone())));
+                candidates.extend(b1.shrink().into_iter().map(|b| rebuild((**b0).clone(), b)));
+                candidates
+            }
+            BexpImpl::Dummy => vec![],
+        }
+    }
+}
+
+impl Com {
+    /// 自身より 1 段小さいコマンドの候補を返します。
+    ///
+    /// 候補は停止するとは限りません。
+    pub fn shrink(&self) -> Vec<Com> {
+        match self {
+            Com::Skip => vec![],
+            Com::Subst(var, a) => {
+                let mut candidates = vec![Com::Skip];
+                candidates.extend(a.shrink().into_iter().map(|a| Com::Subst(var.clone(), a)));
+                candidates
+            }
+            Com::Seq(c0, c1) => {
+                let mut candidates = vec![(**c0).clone(), (**c1).clone()];
+                candidates.extend(
+                    c0.shrink()
+                        .into_iter()
+                        .map(|c| Com::Seq(Box::new(c), c1.clone())),
+                );
+                candidates.extend(
+                    c1.shrink()
+                        .into_iter()
+                        .map(|c| Com::Seq(c0.clone(), Box::new(c))),
+                );
+                candidates
+            }
+            Com::If(b, c0, c1) => {
+                let mut candidates = vec![(**c0).clone(), (**c1).clone()];
+                candidates.extend(
+                    b.shrink()
+                        .into_iter()
+                        .map(|b| Com::If(b, c0.clone(), c1.clone())),
+                );
+                candidates.extend(
+                    c0.shrink()
+                        .into_iter()
+                        .map(|c| Com::If(b.clone(), Box::new(c), c1.clone())),
+                );
+                candidates.extend(
+                    c1.shrink()
+                        .into_iter()
+                        .map(|c| Com::If(b.clone(), c0.clone(), Box::new(c))),
+                );
+                candidates
+            }
+            Com::While(b, c) => {
+                let mut candidates = vec![Com::Skip, (**c).clone()];
+                candidates.extend(b.shrink().into_iter().map(|b| Com::While(b, c.clone())));
+                candidates.extend(
+                    c.shrink()
+                        .into_iter()
+                        .map(|c| Com::While(b.clone(), Box::new(c))),
+                );
+                candidates
+            }
+        }
+    }
+}
+
+/// `fails` が真である範囲で `program` をできるだけ小さくします。
+///
+/// 候補を順に試し、`fails` が真になる最初の候補に置き換えることを、
+/// どの候補でも偽になるまで繰り返します。
+pub fn shrink(program: &Com, fails: impl Fn(&Com) -> bool) -> Com {
+    let mut program = program.clone();
+    while let Some(smaller) = program.shrink().into_iter().find(|c| fails(c)) {
+        program = smaller;
+    }
+    program
+}
+
+/// 2 つの意味論の結果が食い違う入力
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterexample {
+    /// 食い違いが起きる最小のプログラム
+    pub program: Com,
+    /// 初期状態
+    pub state: State,
+    /// 1 つ目の意味論の結果
+    pub left: Option<State>,
+    /// 2 つ目の意味論の結果
+    pub right: Option<State>,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |result: &Option<State>| match result {
+            Some(state) => state.to_string(),
+            None => "undefined".to_string(),
+        };
+        write!(
+            f,
+            "`{}` from {}: {} vs {}",
+            self.program,
+            self.state,
+            show(&self.left),
+            show(&self.right)
+        )
+    }
+}
+
+/// 操作的意味論（[`DEFAULT_FUEL`] の燃料で打ち切り）で実行した結果を返します。
+/// エラーが起きたり燃料を使い切ったりしたら `None` を返します。
+pub fn operational(program: &Com, state: &State) -> Option<State> {
+    let (_, state) = program
+        .try_execute_with_fuel(state.clone(), DEFAULT_FUEL)
+        .ok()?;
+    Some(state)
+}
+
+/// 表示的意味論 𝒞⟦c⟧ の値を返します。
+pub fn denotational(program: &Com, state: &State) -> Option<State> {
+    program.denote()(state)
+}
+
+/// ランダムな `cases` 個のプログラムと初期状態で、`left` と `right` の結果が一致するか確かめます。
+///
+/// 一致しない入力が見つかったら、プログラムを小さくして返します。
+pub fn check_equivalence(
+    generator: &Generator,
+    cases: usize,
+    seed: u64,
+    left: impl Fn(&Com, &State) -> Option<State>,
+    right: impl Fn(&Com, &State) -> Option<State>,
+) -> Result<(), Box<Counterexample>> {
+    let mut rng = Rng::new(seed);
+    for _ in 0..cases {
+        let program = generator.com(&mut rng);
+        let state = generator.state(&mut rng);
+        let differs = |c: &Com| left(c, &state) != right(c, &state);
+        if differs(&program) {
+            let program = shrink(&program, differs);
+            return Err(Box::new(Counterexample {
+                left: left(&program, &state),
+                right: right(&program, &state),
+                program,
+                state,
+            }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            parse_com,
+            random::{check_equivalence, denotational, operational, shrink, Generator, Rng},
+            small_step::Configuration,
+            Aexp, Com,
+        },
+        State,
+    };
+
+    fn small_step(program: &Com, state: &State) -> Option<State> {
+        match program.steps(state.clone()).take(1 << 20).last()? {
+            Ok(Configuration::Terminated(state)) => Some(state),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn generated_programs_terminate() {
+        let gen = Generator::default();
+        let mut rng = Rng::new(1);
+        for _ in 0..200 {
+            let program = gen.com(&mut rng);
+            let state = gen.state(&mut rng);
+            // 生成したプログラムは必ず再び構文解析できる
+            assert_eq!(
+                Ok(program.clone()),
+                parse_com(&program.to_string()),
+                "{}",
+                program
+            );
+            if let Err(e) = program.try_execute_with_fuel(state, 100_000) {
+                assert!(
+                    !matches!(e, crate::imp::ImpError::OutOfFuel(_)),
+                    "{}",
+                    program
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn extreme_generators() {
+        // i32 の全域の整数リテラルでも溢れない
+        let mut rng = Rng::new(2);
+        let values: Vec<i32> = (0..100).map(|_| rng.range(i32::MIN, i32::MAX)).collect();
+        assert!(values.iter().any(|n| *n < 0) && values.iter().any(|n| *n > 0));
+        assert_eq!(7, rng.range(7, 7));
+        assert_eq!(None, rng.choose::<i32>(&[]));
+
+        let wide = Generator {
+            max_literal: i32::MAX,
+            ..Generator::default()
+        };
+        // 変数がなければ代入も変数の参照も生成しない
+        let no_vars = Generator {
+            vars: vec![],
+            ..Generator::default()
+        };
+        for _ in 0..100 {
+            wide.com(&mut rng);
+            wide.state(&mut rng);
+            let program = no_vars.com(&mut rng);
+            assert!(
+                !program.to_string().contains(['X', 'Y', 'Z']),
+                "{}",
+                program
+            );
+            assert_eq!(State::init(), no_vars.state(&mut rng));
+        }
+    }
+
+    #[test]
+    fn semantics_agree() {
+        let gen = Generator::default();
+        assert_eq!(
+            Ok(()),
+            check_equivalence(&gen, 500, 42, operational, denotational)
+        );
+        assert_eq!(
+            Ok(()),
+            check_equivalence(&gen, 500, 43, operational, small_step)
+        );
+    }
+
+    #[test]
+    fn shrink_counterexample() {
+        // 乗算を含むと X を書き換えてしまう誤った意味論
+        let wrong = |program: &Com, state: &State| {
+            let result = operational(program, state)?;
+            if program.to_string().contains('*') {
+                Some(result.update_variable(&"X".into(), 42.into()))
+            } else {
+                Some(result)
+            }
+        };
+        let gen = Generator::default();
+        let e = check_equivalence(&gen, 500, 7, operational, wrong).unwrap_err();
+        assert!(
+            matches!(&e.program, Com::Subst(_, Aexp::Mul(a0, a1))
+                if **a0 == Aexp::N(0.into()) && **a1 == Aexp::N(0.into())),
+            "{}",
+            e
+        );
+        assert_eq!(Some(42.into()), e.right.unwrap().get(&"X".into()).clone());
+    }
+
+    #[test]
+    fn shrink_to_minimal() {
+        let program = parse_com("X := 1; if X <= 2 then { Y := 3 * X; Z := 0 } else skip").unwrap();
+        let fails = |c: &Com| c.to_string().contains("Y :=");
+        assert_eq!(parse_com("Y := 0").unwrap(), shrink(&program, fails));
+    }
+}
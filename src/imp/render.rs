@@ -0,0 +1,306 @@
+//! 導出木の清書
+//!
+//! [`Derivation`] を次の 3 つの形式で出力します。
+//!
+//! * [`Derivation::to_text`]: `Com::execute` のコメントと同じ、`-` の横線で前提と結論を区切る形式
+//! * [`Derivation::to_bussproofs`]: LaTeX の `bussproofs` パッケージの `prooftree` 環境
+//! * [`Derivation::to_mathpartir`]: LaTeX の `mathpartir` パッケージの `\inferrule*`
+
+use std::fmt::{self, Write};
+
+use crate::imp::derivation::{Derivation, Judgement};
+
+impl Derivation {
+    /// 前提を横に並べ、`-` の横線の下に結論を置いた複数行の文字列を返します。
+    ///
+    /// ```text
+    /// --------------------------- loc  --------------------------- num
+    /// ⟨X, {X ↦ 1}⟩ → ⟨1, {X ↦ 1}⟩      ⟨2, {X ↦ 1}⟩ → ⟨2, {X ↦ 1}⟩
+    /// ---------------------------------------------------------------- sum
+    ///                 ⟨X + 2, {X ↦ 1}⟩ → ⟨3, {X ↦ 1}⟩
+    /// ```
+    pub fn to_text(&self) -> String {
+        let block = Block::layout(self);
+        let mut out = String::new();
+        for (i, line) in block.lines.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.push_str(line.trim_end());
+        }
+        out
+    }
+
+    /// `bussproofs` の `prooftree` 環境を返します。
+    pub fn to_bussproofs(&self) -> String {
+        let mut out = String::from("\\begin{prooftree}\n");
+        self.write_bussproofs(&mut out)
+            .expect("writing to a String never fails");
+        out.push_str("\\end{prooftree}");
+        out
+    }
+
+    fn write_bussproofs(&self, out: &mut String) -> fmt::Result {
+        if self.premises.is_empty() {
+            writeln!(out, "\\AxiomC{{}}")?;
+        }
+        for premise in &self.premises {
+            premise.write_bussproofs(out)?;
+        }
+        let inference = match self.premises.len() {
+            0 | 1 => "UnaryInfC",
+            2 => "BinaryInfC",
+            3 => "TrinaryInfC",
+            4 => "QuaternaryInfC",
+            _ => "QuinaryInfC",
+        };
+        writeln!(out, "\\RightLabel{{\\scriptsize {}}}", self.rule)?;
+        writeln!(
+            out,
+            "\\{}{{${}$}}",
+            inference,
+            latex_judgement(&self.conclusion)
+        )
+    }
+
+    /// `mathpartir` の `\inferrule*` を返します。
+    pub fn to_mathpartir(&self) -> String {
+        let mut out = String::new();
+        self.write_mathpartir(&mut out, 0)
+            .expect("writing to a String never fails");
+        out
+    }
+
+    fn write_mathpartir(&self, out: &mut String, level: usize) -> fmt::Result {
+        let indent = "  ".repeat(level);
+        writeln!(out, "\\inferrule*[right={}]", self.rule)?;
+        write!(out, "{}  {{", indent)?;
+        for (i, premise) in self.premises.iter().enumerate() {
+            if i > 0 {
+                write!(out, " \\\\")?;
+            }
+            write!(out, "\n{}    ", indent)?;
+            premise.write_mathpartir(out, level + 2)?;
+        }
+        if !self.premises.is_empty() {
+            write!(out, "\n{}  ", indent)?;
+        }
+        writeln!(out, "}}")?;
+        write!(out, "{}  {{{}}}", indent, latex_judgement(&self.conclusion))
+    }
+}
+
+impl fmt::Display for Derivation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_text())
+    }
+}
+
+/// 各行を同じ幅に揃えた文字の矩形
+struct Block {
+    lines: Vec<String>,
+    width: usize,
+}
+
+impl Block {
+    fn new(lines: Vec<String>) -> Block {
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let lines = lines.into_iter().map(|l| pad(&l, 0, width)).collect();
+        Block { lines, width }
+    }
+
+    fn layout(d: &Derivation) -> Block {
+        let premises = Block::beside(d.premises.iter().map(Block::layout).collect());
+        let conclusion = d.conclusion.to_string();
+        let bar = premises.width.max(conclusion.chars().count());
+
+        let mut lines = Vec::new();
+        for line in &premises.lines {
+            lines.push(center(line, bar));
+        }
+        lines.push(format!("{} {}", "-".repeat(bar), d.rule));
+        lines.push(center(&conclusion, bar));
+        Block::new(lines)
+    }
+
+    /// 下端を揃えて横に並べます。
+    fn beside(blocks: Vec<Block>) -> Block {
+        let height = blocks.iter().map(|b| b.lines.len()).max().unwrap_or(0);
+        let lines = (0..height)
+            .map(|row| {
+                blocks
+                    .iter()
+                    .map(|b| {
+                        let offset = height - b.lines.len();
+                        if row < offset {
+                            " ".repeat(b.width)
+                        } else {
+                            b.lines[row - offset].clone()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ")
+            })
+            .collect();
+        Block::new(lines)
+    }
+}
+
+/// 左に `left` 文字の空白を置き、全体が `width` 文字になるよう右を空白で埋めます。
+fn pad(s: &str, left: usize, width: usize) -> String {
+    let len = s.chars().count();
+    format!(
+        "{}{}{}",
+        " ".repeat(left),
+        s,
+        " ".repeat(width.saturating_sub(left + len))
+    )
+}
+
+fn center(s: &str, width: usize) -> String {
+    let len = s.chars().count();
+    pad(s, width.saturating_sub(len) / 2, width)
+}
+
+fn latex_judgement(j: &Judgement) -> String {
+    let config = |e: String, state: String| format!("\\langle {}, {} \\rangle", e, state);
+    match j {
+        Judgement::Aexp(a, state, n) => {
+            let state = latex(&state.to_string());
+            format!(
+                "{} \\to {}",
+                config(latex(&a.to_string()), state.clone()),
+                config(n.to_string(), state)
+            )
+        }
+        Judgement::Bexp(b, state, t) => {
+            let state = latex(&state.to_string());
+            format!(
+                "{} \\to {}",
+                config(latex(&b.to_string()), state.clone()),
+                config(latex(&t.to_string()), state)
+            )
+        }
+        Judgement::Com(c, state, after) => format!(
+            "{} \\to {}",
+            config(latex(&c.to_string()), latex(&state.to_string())),
+            config("()".to_string(), latex(&after.to_string()))
+        ),
+    }
+}
+
+/// 具象構文や状態の文字列を LaTeX の数式に変換します。
+fn latex(src: &str) -> String {
+    const KEYWORDS: [&str; 11] = [
+        "skip", "if", "then", "else", "while", "do", "true", "false", "not", "and", "or",
+    ];
+
+    let mut out = String::new();
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if c.is_alphabetic() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(&d) = chars.peek() {
+                    if !(d.is_alphanumeric() || d == '_') {
+                        break;
+                    }
+                    word.push(d);
+                    chars.next();
+                }
+                if KEYWORDS.contains(&word.as_str()) {
+                    write!(out, "\\mathbf{{{}}}", word).unwrap();
+                } else if word.chars().count() > 1 {
+                    write!(out, "\\mathit{{{}}}", word.replace('_', "\\_")).unwrap();
+                } else {
+                    out.push_str(&word.replace('_', "\\_"));
+                }
+            }
+            ' ' => out.push_str("\\ "),
+            '<' if chars.peek() == Some(&'=') => {
+                chars.next();
+                out.push_str("\\leq");
+            }
+            ':' if chars.peek() == Some(&'=') => {
+                chars.next();
+                out.push_str("\\mathrel{:=}");
+            }
+            '*' => out.push_str("\\times"),
+            '{' => out.push_str("\\{"),
+            '}' => out.push_str("\\}"),
+            '↦' => out.push_str("\\mapsto"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{parse_aexp, parse_com},
+        State,
+    };
+
+    #[test]
+    fn render_text() {
+        let state = State::from(&[("X", 1.into())]);
+        let d = parse_aexp("X + 2").unwrap().derive(&state).unwrap();
+        assert_eq!(
+            "--------------------------- loc  --------------------------- num\n\
+             ⟨X, {X ↦ 1}⟩ → ⟨1, {X ↦ 1}⟩      ⟨2, {X ↦ 1}⟩ → ⟨2, {X ↦ 1}⟩\n\
+             ---------------------------------------------------------------- sum\n\
+             \x20               ⟨X + 2, {X ↦ 1}⟩ → ⟨3, {X ↦ 1}⟩",
+            d.to_text(),
+        );
+        assert_eq!(d.to_text(), d.to_string());
+    }
+
+    #[test]
+    fn render_bussproofs() {
+        let d = parse_com("X := 2").unwrap().derive(&State::init()).unwrap();
+        assert_eq!(
+            "\\begin{prooftree}\n\
+             \\AxiomC{}\n\
+             \\RightLabel{\\scriptsize num}\n\
+             \\UnaryInfC{$\\langle 2, \\{\\} \\rangle \\to \\langle 2, \\{\\} \\rangle$}\n\
+             \\RightLabel{\\scriptsize assign}\n\
+             \\UnaryInfC{$\\langle X\\ \\mathrel{:=}\\ 2, \\{\\} \\rangle \\to \\langle (), \\{X\\ \\mapsto\\ 2\\} \\rangle$}\n\
+             \\end{prooftree}",
+            d.to_bussproofs(),
+        );
+
+        // while-true は 3 つの前提を持つ
+        let d = parse_com("while X <= 0 do X := X + 1")
+            .unwrap()
+            .derive(&State::from(&[("X", 0.into())]))
+            .unwrap();
+        let bussproofs = d.to_bussproofs();
+        assert!(bussproofs.contains("\\RightLabel{\\scriptsize while-true}\n\\TrinaryInfC{"));
+        assert!(bussproofs.contains("\\mathbf{while}\\ X\\ \\leq\\ 0\\ \\mathbf{do}"));
+
+        // 1 文字の変数名 `_` もエスケープする
+        let d = parse_com("_ := 1").unwrap().derive(&State::init()).unwrap();
+        assert!(d
+            .to_bussproofs()
+            .contains("\\langle \\_\\ \\mathrel{:=}\\ 1, \\{\\} \\rangle \\to \\langle (), \\{\\_\\ \\mapsto\\ 1\\} \\rangle"));
+    }
+
+    #[test]
+    fn render_mathpartir() {
+        let d = parse_aexp("2 * 3").unwrap().derive(&State::init()).unwrap();
+        assert_eq!(
+            "\\inferrule*[right=prod]\n\
+             \x20 {\n\
+             \x20   \\inferrule*[right=num]\n\
+             \x20     {}\n\
+             \x20     {\\langle 2, \\{\\} \\rangle \\to \\langle 2, \\{\\} \\rangle} \\\\\n\
+             \x20   \\inferrule*[right=num]\n\
+             \x20     {}\n\
+             \x20     {\\langle 3, \\{\\} \\rangle \\to \\langle 3, \\{\\} \\rangle}\n\
+             \x20 }\n\
+             \x20 {\\langle 2\\ \\times\\ 3, \\{\\} \\rangle \\to \\langle 6, \\{\\} \\rangle}",
+            d.to_mathpartir(),
+        );
+    }
+}
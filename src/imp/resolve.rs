@@ -0,0 +1,490 @@
+//! 変数をスロットの番号に解決したプログラム
+//!
+//! [`State`] は変数名の文字列をキーとするハッシュ表なので、変数を読み書きするたびに文字列のハッシュを計算します。
+//! 実行する前にプログラムの変数を変数表 [`VarTable`] で一度だけ番号に解決しておけば、
+//! 実行中は配列 [`Store`] を添字で引くだけで済みます。
+//!
+//! ```text
+//! while X <= 9 do X := X + 1    （X はスロット 0）
+//!
+//! while #0 <= 9 do #0 := #0 + 1
+//! ```
+//!
+//! [`Store`] と [`State`] は変数表を通して相互に変換できます。
+//! エラーは変数名を戻した式やコマンドで、インタプリタと同じものを報告します。
+
+use std::collections::HashMap;
+
+use crate::{
+    imp::{interpret, Aexp, Bexp, BexpImpl, Com, ComForm, ComSyntax, ImpError, Memory},
+    Number, State, Truth, VarName,
+};
+
+/// 変数表。変数名とスロットの番号を対応付けます。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VarTable {
+    vars: Vec<VarName>,
+    slots: HashMap<VarName, usize>,
+}
+
+impl VarTable {
+    /// 空の変数表を生成します。
+    pub fn new() -> VarTable {
+        VarTable::default()
+    }
+
+    /// 変数 `x` のスロットの番号を返します。初めて見る変数には新しいスロットを割り当てます。
+    pub fn intern(&mut self, x: &VarName) -> usize {
+        if let Some(&slot) = self.slots.get(x) {
+            return slot;
+        }
+        let slot = self.vars.len();
+        self.vars.push(x.clone());
+        self.slots.insert(x.clone(), slot);
+        slot
+    }
+
+    /// 変数 `x` のスロットの番号を返します。
+    pub fn slot(&self, x: &VarName) -> Option<usize> {
+        self.slots.get(x).copied()
+    }
+
+    /// スロット `slot` の変数名を返します。
+    pub fn name(&self, slot: usize) -> &VarName {
+        &self.vars[slot]
+    }
+
+    /// 割り当てたスロットの数を返します。
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    /// スロットを 1 つも割り当てていなければ `true` を返します。
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// 算術式の変数を解決します。
+    pub fn aexp(&mut self, a: &Aexp) -> ResolvedAexp {
+        let binary = |table: &mut Self, a_0: &Aexp, a_1: &Aexp| {
+            (Box::new(table.aexp(a_0)), Box::new(table.aexp(a_1)))
+        };
+        match a {
+            Aexp::N(n) => ResolvedAexp::N(*n),
+            Aexp::Loc(x) => ResolvedAexp::Loc(self.intern(x)),
+            Aexp::Add(a_0, a_1) => {
+                let (a_0, a_1) = binary(self, a_0, a_1);
+                ResolvedAexp::Add(a_0, a_1)
+            }
+            Aexp::Sub(a_0, a_1) => {
+                let (a_0, a_1) = binary(self, a_0, a_1);
+                ResolvedAexp::Sub(a_0, a_1)
+            }
+            Aexp::Mul(a_0, a_1) => {
+                let (a_0, a_1) = binary(self, a_0, a_1);
+                ResolvedAexp::Mul(a_0, a_1)
+            }
+        }
+    }
+
+    /// ブール式の変数を解決します。
+    pub fn bexp(&mut self, b: &Bexp) -> ResolvedBexp {
+        self.bexp_impl(&b.bexp)
+    }
+
+    fn bexp_impl(&mut self, b: &BexpImpl) -> ResolvedBexp {
+        match b {
+            BexpImpl::T(t) => ResolvedBexp::T(*t),
+            BexpImpl::Eq(a_0, a_1) => ResolvedBexp::Eq(self.aexp(a_0), self.aexp(a_1)),
+            BexpImpl::Le(a_0, a_1) => ResolvedBexp::Le(self.aexp(a_0), self.aexp(a_1)),
+            BexpImpl::Not(b) => ResolvedBexp::Not(Box::new(self.bexp_impl(b))),
+            BexpImpl::And(b_0, b_1) => {
+                ResolvedBexp::And(Box::new(self.bexp_impl(b_0)), Box::new(self.bexp_impl(b_1)))
+            }
+            BexpImpl::Or(b_0, b_1) => {
+                ResolvedBexp::Or(Box::new(self.bexp_impl(b_0)), Box::new(self.bexp_impl(b_1)))
+            }
+            BexpImpl::Dummy => panic!(), // 短絡評価のテスト用
+        }
+    }
+
+    /// コマンドの変数を解決します。
+    pub fn com(&mut self, c: &Com) -> ResolvedCom {
+        match c {
+            Com::Skip => ResolvedCom::Skip,
+            Com::Subst(x, a) => ResolvedCom::Subst(self.intern(x), self.aexp(a)),
+            Com::Seq(c_0, c_1) => {
+                ResolvedCom::Seq(Box::new(self.com(c_0)), Box::new(self.com(c_1)))
+            }
+            Com::If(b, c_0, c_1) => ResolvedCom::If(
+                self.bexp(b),
+                Box::new(self.com(c_0)),
+                Box::new(self.com(c_1)),
+            ),
+            Com::While(b, c) => ResolvedCom::While(self.bexp(b), Box::new(self.com(c))),
+        }
+    }
+
+    /// 状態 `state` の変数のうち、この表にあるものの値を持つストアを生成します。
+    pub fn store(&self, state: &State) -> Store {
+        Store(self.vars.iter().map(|x| *state.get(x)).collect())
+    }
+
+    /// ストア `store` の値を状態 `state` に書き戻した状態を返します。
+    pub fn state(&self, store: &Store, state: State) -> State {
+        let mut state = state;
+        for (slot, n) in store.0.iter().enumerate() {
+            if let Some(n) = n {
+                state = state.update_variable(&self.vars[slot], *n);
+            }
+        }
+        state
+    }
+}
+
+/// ストア。スロットの番号で変数の値を引きます。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Store(Vec<Option<Number>>);
+
+impl Store {
+    /// スロット `slot` の値を返します。
+    pub fn get(&self, slot: usize) -> Option<Number> {
+        self.0.get(slot).copied().flatten()
+    }
+
+    /// スロット `slot` に値 `n` を書き込みます。
+    pub fn set(&mut self, slot: usize, n: Number) {
+        if self.0.len() <= slot {
+            self.0.resize(slot + 1, None);
+        }
+        self.0[slot] = Some(n);
+    }
+}
+
+/// 変数を解決した算術式
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedAexp {
+    /// 整数 n
+    N(Number),
+    /// スロット
+    Loc(usize),
+    /// 加算 `a_0 + a_1`
+    Add(Box<ResolvedAexp>, Box<ResolvedAexp>),
+    /// 減算 `a_0 - a_1`
+    Sub(Box<ResolvedAexp>, Box<ResolvedAexp>),
+    /// 乗算 `a_0 * a_1`
+    Mul(Box<ResolvedAexp>, Box<ResolvedAexp>),
+}
+
+impl ResolvedAexp {
+    /// ストア `store` のもとで自身を評価します。エラーの報告には変数表 `table` の変数名を使います。
+    pub fn try_evaluate(&self, table: &VarTable, store: &Store) -> Result<Number, ImpError> {
+        let (a_0, a_1, op): (_, _, fn(Number, Number) -> Option<Number>) = match self {
+            ResolvedAexp::N(n) => return Ok(*n),
+            ResolvedAexp::Loc(slot) => {
+                return store
+                    .get(*slot)
+                    .ok_or_else(|| ImpError::UndefinedVariable(table.name(*slot).clone()))
+            }
+            ResolvedAexp::Add(a_0, a_1) => (a_0, a_1, Number::checked_add),
+            ResolvedAexp::Sub(a_0, a_1) => (a_0, a_1, Number::checked_sub),
+            ResolvedAexp::Mul(a_0, a_1) => (a_0, a_1, Number::checked_mul),
+        };
+        let n_0 = a_0.try_evaluate(table, store)?;
+        let n_1 = a_1.try_evaluate(table, store)?;
+        op(n_0, n_1).ok_or_else(|| ImpError::Overflow(self.unresolve(table)))
+    }
+
+    /// 変数名に戻した算術式を返します。
+    pub fn unresolve(&self, table: &VarTable) -> Aexp {
+        let binary = |a_0: &ResolvedAexp, a_1: &ResolvedAexp| {
+            (
+                Box::new(a_0.unresolve(table)),
+                Box::new(a_1.unresolve(table)),
+            )
+        };
+        match self {
+            ResolvedAexp::N(n) => Aexp::N(*n),
+            ResolvedAexp::Loc(slot) => Aexp::Loc(table.name(*slot).clone()),
+            ResolvedAexp::Add(a_0, a_1) => {
+                let (a_0, a_1) = binary(a_0, a_1);
+                Aexp::Add(a_0, a_1)
+            }
+            ResolvedAexp::Sub(a_0, a_1) => {
+                let (a_0, a_1) = binary(a_0, a_1);
+                Aexp::Sub(a_0, a_1)
+            }
+            ResolvedAexp::Mul(a_0, a_1) => {
+                let (a_0, a_1) = binary(a_0, a_1);
+                Aexp::Mul(a_0, a_1)
+            }
+        }
+    }
+}
+
+/// 変数を解決したブール式
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedBexp {
+    /// 真偽値 `true`, `false`
+    T(Truth),
+    /// 等値比較 `a_0 = a_1`
+    Eq(ResolvedAexp, ResolvedAexp),
+    /// より小さいか等しい `a_0 <= a_1`
+    Le(ResolvedAexp, ResolvedAexp),
+    /// 否定 `not b`
+    Not(Box<ResolvedBexp>),
+    /// 論理積 `b_0 and b_1`
+    And(Box<ResolvedBexp>, Box<ResolvedBexp>),
+    /// 論理和 `b_0 or b_1`
+    Or(Box<ResolvedBexp>, Box<ResolvedBexp>),
+}
+
+impl ResolvedBexp {
+    /// ストア `store` のもとで自身を短絡評価します。エラーの報告には変数表 `table` の変数名を使います。
+    pub fn try_evaluate(&self, table: &VarTable, store: &Store) -> Result<Truth, ImpError> {
+        Ok(match self {
+            ResolvedBexp::T(t) => *t,
+            ResolvedBexp::Eq(a_0, a_1) => {
+                (a_0.try_evaluate(table, store)? == a_1.try_evaluate(table, store)?).into()
+            }
+            ResolvedBexp::Le(a_0, a_1) => {
+                (a_0.try_evaluate(table, store)? <= a_1.try_evaluate(table, store)?).into()
+            }
+            ResolvedBexp::Not(b) => !b.try_evaluate(table, store)?,
+            ResolvedBexp::And(b_0, b_1) => {
+                if bool::from(b_0.try_evaluate(table, store)?) {
+                    b_1.try_evaluate(table, store)?
+                } else {
+                    Truth(false)
+                }
+            }
+            ResolvedBexp::Or(b_0, b_1) => {
+                if bool::from(b_0.try_evaluate(table, store)?) {
+                    Truth(true)
+                } else {
+                    b_1.try_evaluate(table, store)?
+                }
+            }
+        })
+    }
+
+    /// 変数名に戻したブール式を返します。
+    pub fn unresolve(&self, table: &VarTable) -> Bexp {
+        match self {
+            ResolvedBexp::T(t) => Bexp::truth((*t).into()),
+            ResolvedBexp::Eq(a_0, a_1) => Bexp::eq(a_0.unresolve(table), a_1.unresolve(table)),
+            ResolvedBexp::Le(a_0, a_1) => Bexp::le(a_0.unresolve(table), a_1.unresolve(table)),
+            ResolvedBexp::Not(b) => Bexp::not(b.unresolve(table)),
+            ResolvedBexp::And(b_0, b_1) => Bexp::and(b_0.unresolve(table), b_1.unresolve(table)),
+            ResolvedBexp::Or(b_0, b_1) => Bexp::or(b_0.unresolve(table), b_1.unresolve(table)),
+        }
+    }
+}
+
+/// 変数を解決したコマンド
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedCom {
+    /// 基礎コマンド
+    Skip,
+    /// 代入 `X := a`
+    Subst(usize, ResolvedAexp),
+    /// 逐次実行 `c_0 ; c_1`
+    Seq(Box<ResolvedCom>, Box<ResolvedCom>),
+    /// 条件分岐 `if b then c_0 else c_1`
+    If(ResolvedBexp, Box<ResolvedCom>, Box<ResolvedCom>),
+    /// whileループ `while b do c`
+    While(ResolvedBexp, Box<ResolvedCom>),
+}
+
+impl ResolvedCom {
+    /// 変数名に戻したコマンドを返します。
+    pub fn unresolve(&self, table: &VarTable) -> Com {
+        match self {
+            ResolvedCom::Skip => Com::Skip,
+            ResolvedCom::Subst(slot, a) => {
+                Com::Subst(table.name(*slot).clone(), a.unresolve(table))
+            }
+            ResolvedCom::Seq(c_0, c_1) => Com::Seq(
+                Box::new(c_0.unresolve(table)),
+                Box::new(c_1.unresolve(table)),
+            ),
+            ResolvedCom::If(b, c_0, c_1) => Com::If(
+                b.unresolve(table),
+                Box::new(c_0.unresolve(table)),
+                Box::new(c_1.unresolve(table)),
+            ),
+            ResolvedCom::While(b, c) => {
+                Com::While(b.unresolve(table), Box::new(c.unresolve(table)))
+            }
+        }
+    }
+
+    /// ストア `store` を書き換えながら自身を実行します。
+    /// `fuel` が `Some(n)` のときは [`Com::try_execute_with_fuel`] と同じく規則を適用するたびに 1 ずつ減らし、
+    /// 燃料を使い切ったら [`ImpError::OutOfFuel`] に未実行のコマンドを入れて返します。
+    pub fn try_execute(
+        &self,
+        table: &VarTable,
+        store: &mut Store,
+        fuel: &mut Option<u64>,
+    ) -> Result<(), ImpError> {
+        interpret(self, &mut Slots { table, store }, fuel)
+    }
+}
+
+impl ComSyntax for ResolvedCom {
+    type Var = usize;
+    type Aexp = ResolvedAexp;
+    type Bexp = ResolvedBexp;
+
+    fn form(&self) -> ComForm<'_, Self> {
+        match self {
+            ResolvedCom::Skip => ComForm::Skip,
+            ResolvedCom::Subst(slot, a) => ComForm::Subst(slot, a),
+            ResolvedCom::Seq(c_0, c_1) => ComForm::Seq(c_0, c_1),
+            ResolvedCom::If(b, c_0, c_1) => ComForm::If(b, c_0, c_1),
+            ResolvedCom::While(b, c) => ComForm::While(b, c),
+        }
+    }
+}
+
+/// 変数表 `table` で変数を解決したコマンドを実行するストア
+struct Slots<'a> {
+    table: &'a VarTable,
+    store: &'a mut Store,
+}
+
+impl Memory<ResolvedCom> for Slots<'_> {
+    fn assign(&mut self, slot: &usize, a: &ResolvedAexp) -> Result<(), ImpError> {
+        let n = a.try_evaluate(self.table, self.store)?;
+        self.store.set(*slot, n);
+        Ok(())
+    }
+
+    fn test(&self, b: &ResolvedBexp) -> Result<bool, ImpError> {
+        Ok(b.try_evaluate(self.table, self.store)?.into())
+    }
+
+    fn unexecuted(&self, c: &ResolvedCom) -> Com {
+        c.unresolve(self.table)
+    }
+}
+
+/// 変数表と、それで変数を解決したコマンドの組
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved {
+    /// 変数表
+    pub table: VarTable,
+    /// 変数を解決したコマンド
+    pub com: ResolvedCom,
+}
+
+impl Com {
+    /// 自身の変数をスロットの番号に解決します。
+    pub fn resolve(&self) -> Resolved {
+        let mut table = VarTable::new();
+        let com = table.com(self);
+        Resolved { table, com }
+    }
+}
+
+impl Resolved {
+    /// 状態 `state` をストアに変換して実行し、実行後の状態を返します。
+    pub fn try_execute(&self, state: State) -> Result<State, ImpError> {
+        self.run(state, &mut None)
+    }
+
+    /// 高々 `fuel` 回の規則の適用で実行し、実行後の状態を返します。
+    /// 燃料を使い切った場合は [`ImpError::OutOfFuel`] に未実行のコマンドを入れて返します。
+    pub fn try_execute_with_fuel(&self, state: State, fuel: u64) -> Result<State, ImpError> {
+        self.run(state, &mut Some(fuel))
+    }
+
+    fn run(&self, state: State, fuel: &mut Option<u64>) -> Result<State, ImpError> {
+        let mut store = self.table.store(&state);
+        self.com.try_execute(&self.table, &mut store, fuel)?;
+        Ok(self.table.state(&store, state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            parse_aexp, parse_com,
+            random::{Generator, Rng},
+            resolve::{ResolvedAexp, Store, VarTable},
+            ImpError,
+        },
+        Number, State, VarName,
+    };
+
+    #[test]
+    fn resolve() {
+        let c = parse_com("X := Y + 1; while X <= 9 do X := X * Y").unwrap();
+        let resolved = c.resolve();
+        assert_eq!(2, resolved.table.len());
+        assert_eq!(Some(0), resolved.table.slot(&VarName::from("X")));
+        assert_eq!(Some(1), resolved.table.slot(&VarName::from("Y")));
+        assert_eq!(None, resolved.table.slot(&VarName::from("Z")));
+        assert_eq!(c, resolved.com.unresolve(&resolved.table));
+
+        let mut table = VarTable::new();
+        assert_eq!(
+            ResolvedAexp::Add(
+                Box::new(ResolvedAexp::Loc(0)),
+                Box::new(ResolvedAexp::Loc(0))
+            ),
+            table.aexp(&parse_aexp("X + X").unwrap())
+        );
+        assert_eq!(1, table.len());
+    }
+
+    #[test]
+    fn store() {
+        let mut table = VarTable::new();
+        table.intern(&VarName::from("X"));
+        table.intern(&VarName::from("Y"));
+
+        // 変数表にない Z はストアに入らず、書き戻しても残る
+        let state = State::from(&[("X", 1.into()), ("Z", 3.into())]);
+        let mut store = table.store(&state);
+        assert_eq!(Some(Number(1)), store.get(0));
+        assert_eq!(None, store.get(1));
+
+        store.set(1, 2.into());
+        assert_eq!(
+            State::from(&[("X", 1.into()), ("Y", 2.into()), ("Z", 3.into())]),
+            table.state(&store, state)
+        );
+        assert_eq!(Store::default(), VarTable::new().store(&State::init()));
+    }
+
+    #[test]
+    fn same_as_interpreter() {
+        let gen = Generator::default();
+        let mut rng = Rng::new(24);
+        for _ in 0..500 {
+            let c = gen.com(&mut rng);
+            let state = gen.state(&mut rng);
+            assert_eq!(
+                c.try_execute_with_fuel(state.clone(), 1000)
+                    .map(|(_, state)| state),
+                c.resolve().try_execute_with_fuel(state, 1000),
+                "{}",
+                c
+            );
+        }
+
+        for (program, e) in [
+            ("X := Y + 1", ImpError::UndefinedVariable("Y".into())),
+            (
+                "X := 2147483647; X := X + 1",
+                ImpError::Overflow(parse_aexp("X + 1").unwrap()),
+            ),
+        ] {
+            let c = parse_com(program).unwrap();
+            assert_eq!(Err(e), c.resolve().try_execute(State::init()));
+        }
+    }
+}
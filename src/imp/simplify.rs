@@ -0,0 +1,414 @@
+//! 定数畳み込みと代数的な簡約
+//!
+//! 式とコマンドを葉の側から書き換えて簡単にし、適用した規則を順に報告します。
+//!
+//! ```text
+//! 3 + 5 ⇒ 8          a + 0 ⇒ a          a * 1 ⇒ a          a * 0 ⇒ 0
+//! not not b ⇒ b      true and b ⇒ b     false and b ⇒ false
+//! skip; c ⇒ c        if true then c_0 else c_1 ⇒ c_0       while false do c ⇒ skip
+//! ```
+//!
+//! どの規則も、元のコマンドと同じ状態で停止し、同じ種類のエラーで止まるように書き換えます。
+//! 未定義の変数の読み出しは同じ変数を報告しますが、オーバーフローは簡約した後の式を報告します。
+//! オーバーフローする定数の計算は畳み込みません。
+//! `a * 0 ⇒ 0`, `b and false ⇒ false`, `b or true ⇒ true` は `a` や `b` を評価しなくなるので、
+//! 変数を含まずオーバーフローもしない、評価がエラーにならない `a` や `b` のときだけ適用します。
+
+use std::fmt;
+
+use crate::{
+    imp::{Aexp, Bexp, BexpImpl, Com},
+    State, TryEvaluateRef,
+};
+
+/// 簡約の規則
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// `n + m ⇒ p` などの整数の計算
+    FoldArith,
+    /// `n = m`, `n <= m` の計算
+    FoldCompare,
+    /// `a + 0 ⇒ a`, `0 + a ⇒ a`, `a - 0 ⇒ a`
+    AddZero,
+    /// `a * 1 ⇒ a`, `1 * a ⇒ a`
+    MulOne,
+    /// `a * 0 ⇒ 0`, `0 * a ⇒ 0`（`a` の評価がエラーにならないときだけ）
+    MulZero,
+    /// `not true ⇒ false`, `not false ⇒ true`
+    NotConst,
+    /// `not not b ⇒ b`
+    DoubleNegation,
+    /// `true and b ⇒ b`, `b and true ⇒ b`
+    AndTrue,
+    /// `false and b ⇒ false`, `b and false ⇒ false`（後者は `b` の評価がエラーにならないときだけ）
+    AndFalse,
+    /// `false or b ⇒ b`, `b or false ⇒ b`
+    OrFalse,
+    /// `true or b ⇒ true`, `b or true ⇒ true`（後者は `b` の評価がエラーにならないときだけ）
+    OrTrue,
+    /// `skip; c ⇒ c`, `c; skip ⇒ c`
+    SeqSkip,
+    /// `if true then c_0 else c_1 ⇒ c_0`, `if false then c_0 else c_1 ⇒ c_1`
+    IfConst,
+    /// `while false do c ⇒ skip`
+    WhileFalse,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rule::FoldArith => "fold-arith",
+            Rule::FoldCompare => "fold-compare",
+            Rule::AddZero => "add-zero",
+            Rule::MulOne => "mul-one",
+            Rule::MulZero => "mul-zero",
+            Rule::NotConst => "not-const",
+            Rule::DoubleNegation => "double-negation",
+            Rule::AndTrue => "and-true",
+            Rule::AndFalse => "and-false",
+            Rule::OrFalse => "or-false",
+            Rule::OrTrue => "or-true",
+            Rule::SeqSkip => "seq-skip",
+            Rule::IfConst => "if-const",
+            Rule::WhileFalse => "while-false",
+        })
+    }
+}
+
+impl Aexp {
+    /// 簡約した式と、適用した規則を返します。
+    pub fn simplify(&self) -> (Aexp, Vec<Rule>) {
+        let mut rules = vec![];
+        let a = simplify_aexp(self, &mut rules);
+        (a, rules)
+    }
+}
+
+impl Bexp {
+    /// 簡約した式と、適用した規則を返します。
+    pub fn simplify(&self) -> (Bexp, Vec<Rule>) {
+        let mut rules = vec![];
+        let b = simplify_bexp(&self.bexp, &mut rules);
+        (Bexp { bexp: b }, rules)
+    }
+}
+
+impl Com {
+    /// 簡約したコマンドと、適用した規則を返します。
+    pub fn simplify(&self) -> (Com, Vec<Rule>) {
+        let mut rules = vec![];
+        let c = simplify_com(self, &mut rules);
+        (c, rules)
+    }
+}
+
+fn simplify_aexp(a: &Aexp, rules: &mut Vec<Rule>) -> Aexp {
+    let (a_0, a_1) = match a {
+        Aexp::N(_) | Aexp::Loc(_) => return a.clone(),
+        Aexp::Add(a_0, a_1) | Aexp::Sub(a_0, a_1) | Aexp::Mul(a_0, a_1) => (a_0, a_1),
+    };
+    let a_0 = simplify_aexp(a_0, rules);
+    let a_1 = simplify_aexp(a_1, rules);
+
+    let (rule, result) = match (a, &a_0, &a_1) {
+        (Aexp::Mul(..), Aexp::N(n), other) | (Aexp::Mul(..), other, Aexp::N(n))
+            if *n == 0 && infallible_aexp(other) =>
+        {
+            (Some(Rule::MulZero), Aexp::N(0.into()))
+        }
+        (_, Aexp::N(n), Aexp::N(m)) => {
+            let p = match a {
+                Aexp::Add(..) => n.checked_add(*m),
+                Aexp::Sub(..) => n.checked_sub(*m),
+                _ => n.checked_mul(*m),
+            };
+            match p {
+                Some(p) => (Some(Rule::FoldArith), Aexp::N(p)),
+                None => (None, rebuild(a, a_0, a_1)),
+            }
+        }
+        (Aexp::Add(..), Aexp::N(n), _) if *n == 0 => (Some(Rule::AddZero), a_1),
+        (Aexp::Add(..) | Aexp::Sub(..), _, Aexp::N(n)) if *n == 0 => (Some(Rule::AddZero), a_0),
+        (Aexp::Mul(..), Aexp::N(n), _) if *n == 1 => (Some(Rule::MulOne), a_1),
+        (Aexp::Mul(..), _, Aexp::N(n)) if *n == 1 => (Some(Rule::MulOne), a_0),
+        _ => (None, rebuild(a, a_0, a_1)),
+    };
+    rules.extend(rule);
+    result
+}
+
+/// `a` と同じ演算子で、両辺を `a_0`, `a_1` にした式を返します。
+fn rebuild(a: &Aexp, a_0: Aexp, a_1: Aexp) -> Aexp {
+    let (a_0, a_1) = (Box::new(a_0), Box::new(a_1));
+    match a {
+        Aexp::Add(..) => Aexp::Add(a_0, a_1),
+        Aexp::Sub(..) => Aexp::Sub(a_0, a_1),
+        Aexp::Mul(..) => Aexp::Mul(a_0, a_1),
+        Aexp::N(_) | Aexp::Loc(_) => unreachable!(),
+    }
+}
+
+/// どの状態でも評価がエラーにならない（変数を読まず、オーバーフローしない）なら `true` を返します。
+///
+/// 変数を読まない評価は状態によらないので、空の状態で評価できるかを調べれば十分です。
+fn infallible_aexp(a: &Aexp) -> bool {
+    a.try_evaluate_ref(&State::init()).is_ok()
+}
+
+/// どの状態でも評価がエラーにならないなら `true` を返します。
+fn infallible_bexp(b: &BexpImpl) -> bool {
+    Bexp { bexp: b.clone() }
+        .try_evaluate_ref(&State::init())
+        .is_ok()
+}
+
+/// 真偽値の定数なら、その値を返します。
+fn constant(b: &BexpImpl) -> Option<bool> {
+    match b {
+        BexpImpl::T(t) => Some((*t).into()),
+        _ => None,
+    }
+}
+
+fn simplify_bexp(b: &BexpImpl, rules: &mut Vec<Rule>) -> BexpImpl {
+    let (rule, result) = match b {
+        BexpImpl::T(_) | BexpImpl::Dummy => return b.clone(),
+        BexpImpl::Eq(a_0, a_1) | BexpImpl::Le(a_0, a_1) => {
+            let a_0 = simplify_aexp(a_0, rules);
+            let a_1 = simplify_aexp(a_1, rules);
+            match (&a_0, &a_1) {
+                (Aexp::N(n), Aexp::N(m)) => {
+                    let t = if let BexpImpl::Eq(..) = b {
+                        n == m
+                    } else {
+                        n <= m
+                    };
+                    (Some(Rule::FoldCompare), BexpImpl::T(t.into()))
+                }
+                _ if matches!(b, BexpImpl::Eq(..)) => (None, BexpImpl::Eq(a_0, a_1)),
+                _ => (None, BexpImpl::Le(a_0, a_1)),
+            }
+        }
+        BexpImpl::Not(b) => match simplify_bexp(b, rules) {
+            BexpImpl::T(t) => (Some(Rule::NotConst), BexpImpl::T(!t)),
+            BexpImpl::Not(b) => (Some(Rule::DoubleNegation), *b),
+            b => (None, BexpImpl::Not(Box::new(b))),
+        },
+        BexpImpl::And(b_0, b_1) | BexpImpl::Or(b_0, b_1) => {
+            let is_and = matches!(b, BexpImpl::And(..));
+            let b_0 = simplify_bexp(b_0, rules);
+            let b_1 = simplify_bexp(b_1, rules);
+            // and では true が、or では false が単位元
+            let (unit, absorbing) = if is_and {
+                (Rule::AndTrue, Rule::AndFalse)
+            } else {
+                (Rule::OrFalse, Rule::OrTrue)
+            };
+            match (constant(&b_0), constant(&b_1)) {
+                (Some(t), _) if t == is_and => (Some(unit), b_1),
+                (_, Some(t)) if t == is_and => (Some(unit), b_0),
+                // 左辺が吸収元なら右辺は短絡評価で評価されない
+                (Some(_), _) => (Some(absorbing), BexpImpl::T((!is_and).into())),
+                (_, Some(_)) if infallible_bexp(&b_0) => {
+                    (Some(absorbing), BexpImpl::T((!is_and).into()))
+                }
+                _ if is_and => (None, BexpImpl::And(Box::new(b_0), Box::new(b_1))),
+                _ => (None, BexpImpl::Or(Box::new(b_0), Box::new(b_1))),
+            }
+        }
+    };
+    rules.extend(rule);
+    result
+}
+
+fn simplify_com(c: &Com, rules: &mut Vec<Rule>) -> Com {
+    match c {
+        Com::Skip => Com::Skip,
+        Com::Subst(x, a) => Com::Subst(x.clone(), simplify_aexp(a, rules)),
+        Com::Seq(c_0, c_1) => match (simplify_com(c_0, rules), simplify_com(c_1, rules)) {
+            (Com::Skip, c) | (c, Com::Skip) => {
+                rules.push(Rule::SeqSkip);
+                c
+            }
+            (c_0, c_1) => Com::Seq(Box::new(c_0), Box::new(c_1)),
+        },
+        Com::If(b, c_0, c_1) => {
+            let b = simplify_bexp(&b.bexp, rules);
+            match constant(&b) {
+                Some(t) => {
+                    rules.push(Rule::IfConst);
+                    simplify_com(if t { c_0 } else { c_1 }, rules)
+                }
+                None => Com::If(
+                    Bexp { bexp: b },
+                    Box::new(simplify_com(c_0, rules)),
+                    Box::new(simplify_com(c_1, rules)),
+                ),
+            }
+        }
+        Com::While(b, c) => {
+            let b = simplify_bexp(&b.bexp, rules);
+            if constant(&b) == Some(false) {
+                rules.push(Rule::WhileFalse);
+                Com::Skip
+            } else {
+                Com::While(Bexp { bexp: b }, Box::new(simplify_com(c, rules)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            parse_aexp, parse_bexp, parse_com,
+            random::{check_equivalence, operational, Generator, Rng},
+            simplify::Rule,
+            Com, ImpError,
+        },
+        State, TryExecute,
+    };
+
+    #[test]
+    fn aexp() {
+        let (a, rules) = parse_aexp("(3 + 5) * X + (4 - 1) * (2 - 2) + Z * 1")
+            .unwrap()
+            .simplify();
+        assert_eq!("8 * X + Z", a.to_string());
+        assert_eq!(
+            vec![
+                Rule::FoldArith,
+                Rule::FoldArith,
+                Rule::FoldArith,
+                Rule::MulZero,
+                Rule::AddZero,
+                Rule::MulOne
+            ],
+            rules
+        );
+
+        // オーバーフローする計算は畳み込まない
+        let (a, rules) = parse_aexp("2147483647 + 1").unwrap().simplify();
+        assert_eq!("2147483647 + 1", a.to_string());
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn bexp() {
+        let (b, rules) = parse_bexp("not not X <= 3 and (1 <= 2 or Y = 0)")
+            .unwrap()
+            .simplify();
+        assert_eq!("X <= 3", b.to_string());
+        assert_eq!(
+            vec![
+                Rule::DoubleNegation,
+                Rule::FoldCompare,
+                Rule::OrTrue,
+                Rule::AndTrue
+            ],
+            rules
+        );
+
+        let (b, rules) = parse_bexp("not (1 = 2 and X = 0)").unwrap().simplify();
+        assert_eq!("true", b.to_string());
+        assert_eq!(
+            vec![Rule::FoldCompare, Rule::AndFalse, Rule::NotConst],
+            rules
+        );
+    }
+
+    #[test]
+    fn com() {
+        let (c, rules) = parse_com(
+            "skip; if 1 <= 0 then X := 1 else { X := Y * 1; while false and X = 0 do X := 0 }",
+        )
+        .unwrap()
+        .simplify();
+        assert_eq!("X := Y", c.to_string());
+        assert_eq!(
+            vec![
+                Rule::FoldCompare,
+                Rule::IfConst,
+                Rule::MulOne,
+                Rule::AndFalse,
+                Rule::WhileFalse,
+                Rule::SeqSkip,
+                Rule::SeqSkip
+            ],
+            rules
+        );
+    }
+
+    #[test]
+    fn keeps_errors() {
+        // X が未定義なら X * 0 の評価はエラーなので、0 にしない
+        let (c, rules) = parse_com("Y := X * 0; if X = 0 and false then skip else skip")
+            .unwrap()
+            .simplify();
+        assert_eq!(
+            "Y := X * 0; if X = 0 and false then skip else skip",
+            c.to_string()
+        );
+        assert!(rules.is_empty());
+        assert_eq!(
+            Err(ImpError::UndefinedVariable("X".into())),
+            c.try_execute(State::init())
+        );
+
+        // オーバーフローする右辺も残す
+        let (b, rules) = parse_bexp("2147483647 + 1 <= 0 or true")
+            .unwrap()
+            .simplify();
+        assert_eq!("2147483647 + 1 <= 0 or true", b.to_string());
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn preserves_semantics() {
+        let gen = Generator::default();
+        let result = check_equivalence(&gen, 500, 21, operational, |program, state| {
+            operational(&program.simplify().0, state)
+        });
+        assert_eq!(Ok(()), result.map_err(|e| e.to_string()));
+    }
+
+    #[test]
+    fn preserves_error_kinds() {
+        // オーバーフローは式を除いて、未定義の変数は変数まで比べる
+        let kind = |result: Result<(Option<Com>, State), ImpError>| match result {
+            Ok((_, state)) => Ok(state),
+            Err(ImpError::Overflow(_)) => Err(None),
+            Err(e) => Err(Some(e)),
+        };
+        let gen = Generator {
+            max_literal: 100_000,
+            ..Generator::default()
+        };
+        let mut rng = Rng::new(21);
+        let mut errors = 0;
+        for _ in 0..500 {
+            let program = gen.com(&mut rng);
+            let (simplified, _) = program.simplify();
+            for state in [gen.state(&mut rng), State::init()] {
+                let expected = kind(program.try_execute(state.clone()));
+                errors += expected.is_err() as usize;
+                assert_eq!(
+                    expected,
+                    kind(simplified.try_execute(state)),
+                    "{}",
+                    program
+                );
+            }
+        }
+        assert!(errors > 0);
+
+        // 大きい X では簡約の前後ともオーバーフローするが、報告する式は異なる
+        let c = parse_com("Y := X * (1 + 1)").unwrap();
+        let state = State::from(&[("X", 2_000_000_000.into())]);
+        assert_eq!(
+            Err(ImpError::Overflow(parse_aexp("X * 2").unwrap())),
+            c.simplify().0.try_execute(state)
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! IMP の small-step 操作的意味論
+//!
+//! 1 ステップの遷移 `⟨c, σ⟩ →₁ ⟨c', σ'⟩` または `⟨c, σ⟩ →₁ σ'` を定めます。
+//! 式は左から順に 1 ステップずつ書き換え、値（整数または真偽値）になったら止まります。
+//!
+//! ```text
+//! ⟨X, σ⟩ →₁ ⟨σ(X), σ⟩
+//!
+//!      ⟨a_0, σ⟩ →₁ ⟨a_0', σ⟩                  ⟨a_1, σ⟩ →₁ ⟨a_1', σ⟩
+//! -------------------------------      -----------------------------
+//! ⟨a_0 + a_1, σ⟩ →₁ ⟨a_0' + a_1, σ⟩    ⟨n + a_1, σ⟩ →₁ ⟨n + a_1', σ⟩
+//!
+//! ⟨n + m, σ⟩ →₁ ⟨p, σ⟩  （p は n と m の和）
+//!
+//! ⟨false and b, σ⟩ →₁ ⟨false, σ⟩    ⟨true and b, σ⟩ →₁ ⟨b, σ⟩
+//!
+//! ⟨skip, σ⟩ →₁ σ    ⟨X := n, σ⟩ →₁ σ[n/X]
+//!
+//!    ⟨c_0, σ⟩ →₁ ⟨c_0', σ'⟩                  ⟨c_0, σ⟩ →₁ σ'
+//! -------------------------------    ---------------------------
+//! ⟨c_0; c_1, σ⟩ →₁ ⟨c_0'; c_1, σ'⟩    ⟨c_0; c_1, σ⟩ →₁ ⟨c_1, σ'⟩
+//!
+//! ⟨if true then c_0 else c_1, σ⟩ →₁ ⟨c_0, σ⟩
+//!
+//! ⟨while b do c, σ⟩ →₁ ⟨if b then { c; while b do c } else skip, σ⟩
+//! ```
+//!
+//! `and`/`or` の短絡評価は big-step の [`Evaluate`](crate::Evaluate) と同じです。
+
+use std::fmt;
+
+use crate::{
+    arith::{Arithmetic, Checked},
+    imp::{Aexp, Bexp, BexpImpl, Com, ImpError},
+    State, Truth,
+};
+
+/// コマンドの構成
+#[derive(Debug, Clone, PartialEq)]
+pub enum Configuration {
+    /// 実行途中の構成 `⟨c, σ⟩`
+    Running(Com, State),
+    /// 実行を終えた状態 `σ`
+    Terminated(State),
+}
+
+impl fmt::Display for Configuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Configuration::Running(c, state) => write!(f, "⟨{}, {}⟩", c, state),
+            Configuration::Terminated(state) => write!(f, "{}", state),
+        }
+    }
+}
+
+impl Aexp {
+    /// `⟨a, σ⟩ →₁ ⟨a', σ⟩` となる `a'` を返します。
+    /// 自身が整数ならそれ以上遷移しないので `None` を返します。
+    pub fn step(&self, state: &State) -> Result<Option<Aexp>, ImpError> {
+        let (left, right, op, rebuild): (_, _, fn(_, _) -> _, fn(_, _) -> _) = match self {
+            Aexp::N(_) => return Ok(None),
+            Aexp::Loc(var) => {
+                return match state.get(var) {
+                    Some(n) => Ok(Some(Aexp::N(*n))),
+                    None => Err(ImpError::UndefinedVariable(var.to_owned())),
+                }
+            }
+            Aexp::Add(left, right) => (left, right, Checked::add, Aexp::Add),
+            Aexp::Sub(left, right) => (left, right, Checked::sub, Aexp::Sub),
+            Aexp::Mul(left, right) => (left, right, Checked::mul, Aexp::Mul),
+        };
+
+        if let Some(left) = left.step(state)? {
+            return Ok(Some(rebuild(Box::new(left), right.clone())));
+        }
+        if let Some(right) = right.step(state)? {
+            return Ok(Some(rebuild(left.clone(), Box::new(right))));
+        }
+        let (Aexp::N(n), Aexp::N(m)) = (left.as_ref(), right.as_ref()) else {
+            unreachable!("both operands are numerals")
+        };
+        match op(*n, *m) {
+            Some(p) => Ok(Some(Aexp::N(p))),
+            None => Err(ImpError::Overflow(self.clone())),
+        }
+    }
+}
+
+impl Bexp {
+    /// `⟨b, σ⟩ →₁ ⟨b', σ⟩` となる `b'` を返します。
+    /// 自身が真偽値ならそれ以上遷移しないので `None` を返します。
+    pub fn step(&self, state: &State) -> Result<Option<Bexp>, ImpError> {
+        Ok(self.bexp.step(state)?.map(|bexp| Bexp { bexp }))
+    }
+}
+
+impl BexpImpl {
+    fn step(&self, state: &State) -> Result<Option<BexpImpl>, ImpError> {
+        Ok(Some(match self {
+            BexpImpl::T(_) => return Ok(None),
+            BexpImpl::Eq(left, right) | BexpImpl::Le(left, right) => {
+                let rebuild = match self {
+                    BexpImpl::Eq(..) => BexpImpl::Eq,
+                    _ => BexpImpl::Le,
+                };
+                if let Some(left) = left.step(state)? {
+                    rebuild(left, right.clone())
+                } else if let Some(right) = right.step(state)? {
+                    rebuild(left.clone(), right)
+                } else {
+                    let (Aexp::N(n), Aexp::N(m)) = (left, right) else {
+                        unreachable!("both operands are numerals")
+                    };
+                    BexpImpl::T(Truth(match self {
+                        BexpImpl::Eq(..) => n == m,
+                        _ => n <= m,
+                    }))
+                }
+            }
+            BexpImpl::Not(b) => match b.as_ref() {
+                BexpImpl::T(t) => BexpImpl::T(!*t),
+                b => BexpImpl::Not(Box::new(
+                    b.step(state)?.expect("a non-value expression always steps"),
+                )),
+            },
+            BexpImpl::And(left, right) => match left.as_ref() {
+                BexpImpl::T(Truth(false)) => BexpImpl::T(Truth(false)),
+                BexpImpl::T(Truth(true)) => right.as_ref().clone(),
+                l => BexpImpl::And(
+                    Box::new(l.step(state)?.expect("a non-value expression always steps")),
+                    right.clone(),
+                ),
+            },
+            BexpImpl::Or(left, right) => match left.as_ref() {
+                BexpImpl::T(Truth(true)) => BexpImpl::T(Truth(true)),
+                BexpImpl::T(Truth(false)) => right.as_ref().clone(),
+                l => BexpImpl::Or(
+                    Box::new(l.step(state)?.expect("a non-value expression always steps")),
+                    right.clone(),
+                ),
+            },
+            BexpImpl::Dummy => panic!(), // 短絡評価のテスト用
+        }))
+    }
+}
+
+impl Com {
+    /// `⟨c, σ⟩` から 1 ステップ遷移した構成を返します。
+    pub fn step(&self, state: State) -> Result<Configuration, ImpError> {
+        let running = |c: Com, state: State| Ok(Configuration::Running(c, state));
+        match self {
+            Com::Skip => Ok(Configuration::Terminated(state)),
+            Com::Subst(var, a) => match a.step(&state)? {
+                Some(a) => running(Com::Subst(var.to_owned(), a), state),
+                None => {
+                    let Aexp::N(n) = a else {
+                        unreachable!("a is a numeral")
+                    };
+                    Ok(Configuration::Terminated(state.update_variable(var, *n)))
+                }
+            },
+            Com::Seq(c_0, c_1) => match c_0.step(state)? {
+                Configuration::Running(c_0, state) => {
+                    running(Com::Seq(Box::new(c_0), c_1.clone()), state)
+                }
+                Configuration::Terminated(state) => running(c_1.as_ref().clone(), state),
+            },
+            Com::If(b, c_0, c_1) => match b.step(&state)? {
+                Some(b) => running(Com::If(b, c_0.clone(), c_1.clone()), state),
+                None => {
+                    let BexpImpl::T(Truth(t)) = b.bexp else {
+                        unreachable!("b is a truth value")
+                    };
+                    running(if t { c_0 } else { c_1 }.as_ref().clone(), state)
+                }
+            },
+            Com::While(b, c) => running(
+                Com::If(
+                    b.clone(),
+                    Box::new(Com::Seq(c.clone(), Box::new(self.clone()))),
+                    Box::new(Com::Skip),
+                ),
+                state,
+            ),
+        }
+    }
+
+    /// `⟨c, σ⟩` から始まる実行列 `⟨c, σ⟩ →₁ ⟨c', σ'⟩ →₁ ...` の各構成を順に返すイテレータを生成します。
+    pub fn steps(&self, state: State) -> Steps {
+        Steps {
+            next: Some(Ok(Configuration::Running(self.clone(), state))),
+        }
+    }
+}
+
+/// 実行列の各構成を返すイテレータ
+///
+/// 最初に `⟨c, σ⟩` 自身を返し、実行が終わるかエラーが起きたら止まります。
+/// 停止しないプログラムでは無限に続きます。
+#[derive(Debug, Clone)]
+pub struct Steps {
+    next: Option<Result<Configuration, ImpError>>,
+}
+
+impl Iterator for Steps {
+    type Item = Result<Configuration, ImpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next.take()?;
+        if let Ok(Configuration::Running(c, state)) = &current {
+            self.next = Some(c.step(state.clone()));
+        }
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            parse_aexp, parse_bexp, parse_com,
+            small_step::Configuration::{self, Running, Terminated},
+            Bexp, Com, ImpError,
+        },
+        Execute, State,
+    };
+
+    #[test]
+    fn step_aexp() {
+        // ⟨(X + 2) * 3, σ⟩ →₁ ⟨(1 + 2) * 3, σ⟩ →₁ ⟨3 * 3, σ⟩ →₁ ⟨9, σ⟩
+        let state = State::from(&[("X", 1.into())]);
+        let a = parse_aexp("(X + 2) * 3").unwrap();
+        let a = a.step(&state).unwrap().unwrap();
+        assert_eq!(parse_aexp("(1 + 2) * 3").unwrap(), a);
+        let a = a.step(&state).unwrap().unwrap();
+        assert_eq!(parse_aexp("3 * 3").unwrap(), a);
+        let a = a.step(&state).unwrap().unwrap();
+        assert_eq!(parse_aexp("9").unwrap(), a);
+        assert_eq!(Ok(None), a.step(&state));
+    }
+
+    #[test]
+    fn step_bexp_short_circuit() {
+        // ⟨1 <= 0 and X = 0, σ₀⟩ →₁ ⟨false and X = 0, σ₀⟩ →₁ ⟨false, σ₀⟩
+        let state = State::init();
+        let b = parse_bexp("1 <= 0 and X = 0").unwrap();
+        let b = b.step(&state).unwrap().unwrap();
+        assert_eq!(parse_bexp("false and X = 0").unwrap(), b);
+        let b = b.step(&state).unwrap().unwrap();
+        assert_eq!(Bexp::truth(false), b);
+
+        // ⟨true or X = 0, σ₀⟩ →₁ ⟨true, σ₀⟩
+        let b = parse_bexp("true or X = 0").unwrap();
+        assert_eq!(Ok(Some(Bexp::truth(true))), b.step(&state));
+    }
+
+    #[test]
+    fn execution_sequence() {
+        // ⟨while X <= 0 do X := X + 1, σ⟩ →₁ ... →₁ σ[1/X]
+        let com = parse_com("while X <= 0 do X := X + 1").unwrap();
+        let state = State::from(&[("X", 0.into())]);
+        let sequence: Vec<Configuration> = com.steps(state.clone()).map(Result::unwrap).collect();
+
+        let running =
+            |src: &str, x: i32| Running(parse_com(src).unwrap(), State::from(&[("X", x.into())]));
+        assert_eq!(
+            vec![
+                running("while X <= 0 do X := X + 1", 0),
+                running(
+                    "if X <= 0 then { X := X + 1; while X <= 0 do X := X + 1 } else skip",
+                    0
+                ),
+                running(
+                    "if 0 <= 0 then { X := X + 1; while X <= 0 do X := X + 1 } else skip",
+                    0
+                ),
+                running(
+                    "if true then { X := X + 1; while X <= 0 do X := X + 1 } else skip",
+                    0
+                ),
+                running("X := X + 1; while X <= 0 do X := X + 1", 0),
+                running("X := 0 + 1; while X <= 0 do X := X + 1", 0),
+                running("X := 1; while X <= 0 do X := X + 1", 0),
+                running("while X <= 0 do X := X + 1", 1),
+                running(
+                    "if X <= 0 then { X := X + 1; while X <= 0 do X := X + 1 } else skip",
+                    1
+                ),
+                running(
+                    "if 1 <= 0 then { X := X + 1; while X <= 0 do X := X + 1 } else skip",
+                    1
+                ),
+                running(
+                    "if false then { X := X + 1; while X <= 0 do X := X + 1 } else skip",
+                    1
+                ),
+                running("skip", 1),
+                Terminated(State::from(&[("X", 1.into())])),
+            ],
+            sequence,
+        );
+
+        // 最後の状態は big-step の実行結果と一致する
+        let (None, expected) = com.execute(state) else {
+            panic!()
+        };
+        assert_eq!(Some(Terminated(expected)), sequence.last().cloned());
+    }
+
+    #[test]
+    fn execution_sequence_error() {
+        let mut steps = Com::Subst("X".into(), parse_aexp("Y").unwrap()).steps(State::init());
+        assert!(matches!(steps.next(), Some(Ok(Running(..)))));
+        assert_eq!(
+            Some(Err(ImpError::UndefinedVariable("Y".into()))),
+            steps.next()
+        );
+        assert_eq!(None, steps.next());
+    }
+
+    #[test]
+    fn display_configuration() {
+        assert_eq!(
+            "⟨X := 1; skip, {X ↦ 0, Y ↦ 2}⟩",
+            Running(
+                parse_com("X := 1; skip").unwrap(),
+                State::from(&[("Y", 2.into()), ("X", 0.into())]),
+            )
+            .to_string(),
+        );
+    }
+}
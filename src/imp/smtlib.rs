@@ -0,0 +1,430 @@
+//! SMT-LIB2 形式への書き出し
+//!
+//! 検証条件を SMT ソルバに渡せる形にします。
+//! プログラム変数と自由な整数変数はどれも `Int` の定数として宣言し、
+//! 検証条件 `V` ごとに `(assert (not V))` を `(check-sat)` します。
+//! すべて `unsat` なら検証条件はすべて妥当です。
+//!
+//! `let` のような予約語と同じ名前は `|let|` と囲み、`div` のような理論の記号と同じ名前や、
+//! プログラム変数と重なる整数変数の名前には `'` を付けて区別します。
+//!
+//! ```text
+//! (set-logic QF_NIA)
+//! (declare-const X Int)
+//! (push 1)
+//! ; 0 <= X ⇒ 0 <= X
+//! (assert (not (=> (<= 0 X) (<= 0 X))))
+//! (check-sat)
+//! (pop 1)
+//! (exit)
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write},
+};
+
+use crate::{
+    imp::{
+        assn::{Aexpv, Assn, IntVar},
+        vcgen::{verification_conditions, ACom},
+        Com,
+    },
+    Number, VarName,
+};
+
+/// SMT-LIB2 の予約語とコマンド名。`|...|` で囲めば記号として使えます。
+const RESERVED: &[&str] = &[
+    "!",
+    "_",
+    "as",
+    "BINARY",
+    "DECIMAL",
+    "exists",
+    "forall",
+    "HEXADECIMAL",
+    "let",
+    "match",
+    "NUMERAL",
+    "par",
+    "STRING",
+    "assert",
+    "echo",
+    "exit",
+    "pop",
+    "push",
+    "reset",
+];
+
+/// Core と Ints の理論が定める記号。囲んでも同じ記号なので、別の名前にします。
+const BUILTIN: &[&str] = &[
+    "true", "false", "not", "and", "or", "xor", "distinct", "ite", "div", "mod", "abs", "Int",
+    "Bool",
+];
+
+/// 名前を SMT-LIB2 の記号にします。使えない文字を含むか予約語なら `|...|` で囲みます。
+fn symbol(name: &str) -> String {
+    let simple = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "~!@$%^&*_-+=<>.?/".contains(c))
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && !RESERVED.contains(&name);
+    if simple {
+        name.to_string()
+    } else {
+        format!("|{}|", name)
+    }
+}
+
+/// プログラム変数と整数変数の名前を SMT-LIB2 の記号に対応付けます。
+///
+/// 整数変数の名前がプログラム変数と重なるときや、名前が理論の記号と重なるときは、
+/// どのプログラム変数とも整数変数とも理論の記号とも重ならなくなるまで `'` を付けて区別します。
+struct Names {
+    locations: BTreeMap<VarName, String>,
+    int_vars: BTreeMap<IntVar, String>,
+}
+
+impl Names {
+    fn new(locations: &BTreeSet<VarName>, int_vars: &BTreeSet<IntVar>) -> Names {
+        let mut taken: BTreeSet<String> = locations.iter().map(|x| x.to_string()).collect();
+        taken.extend(int_vars.iter().map(|i| i.to_string()));
+
+        let mut fresh = |mut name: String, clash: bool| {
+            if clash || BUILTIN.contains(&name.as_str()) {
+                while taken.contains(&name) || BUILTIN.contains(&name.as_str()) {
+                    name.push('\'');
+                }
+                taken.insert(name.clone());
+            }
+            symbol(&name)
+        };
+        let locations: BTreeMap<_, _> = locations
+            .iter()
+            .map(|x| (x.clone(), fresh(x.to_string(), false)))
+            .collect();
+        let int_vars = int_vars
+            .iter()
+            .map(|i| {
+                let clash = locations.contains_key(&VarName::from(i.to_string()));
+                (i.clone(), fresh(i.to_string(), clash))
+            })
+            .collect();
+        Names {
+            locations,
+            int_vars,
+        }
+    }
+
+    /// 表明に現れるすべての名前から対応を作ります。
+    fn of<'a>(assns: impl IntoIterator<Item = &'a Assn>, locations: &BTreeSet<VarName>) -> Names {
+        let mut locations = locations.clone();
+        let mut int_vars = BTreeSet::new();
+        for a in assns {
+            locations.extend(a.locations());
+            collect_int_vars(a, &mut int_vars);
+        }
+        Names::new(&locations, &int_vars)
+    }
+
+    fn location(&self, x: &VarName) -> &str {
+        &self.locations[x]
+    }
+
+    fn int_var(&self, i: &IntVar) -> &str {
+        &self.int_vars[i]
+    }
+
+    fn number(n: Number) -> String {
+        if n < Number::from(0) {
+            format!("(- {})", n.to_string().trim_start_matches('-'))
+        } else {
+            n.to_string()
+        }
+    }
+
+    fn write_term(&self, f: &mut impl Write, a: &Aexpv) -> fmt::Result {
+        match a {
+            Aexpv::N(n) => f.write_str(&Names::number(*n)),
+            Aexpv::Loc(x) => f.write_str(self.location(x)),
+            Aexpv::Var(i) => f.write_str(self.int_var(i)),
+            Aexpv::Add(a0, a1) | Aexpv::Sub(a0, a1) | Aexpv::Mul(a0, a1) => {
+                let op = match a {
+                    Aexpv::Add(..) => "+",
+                    Aexpv::Sub(..) => "-",
+                    _ => "*",
+                };
+                write!(f, "({} ", op)?;
+                self.write_term(f, a0)?;
+                f.write_char(' ')?;
+                self.write_term(f, a1)?;
+                f.write_char(')')
+            }
+        }
+    }
+
+    fn write_formula(&self, f: &mut impl Write, a: &Assn) -> fmt::Result {
+        let (op, args): (_, Vec<&Assn>) = match a {
+            Assn::T(t) => return write!(f, "{}", t),
+            Assn::Eq(a0, a1) | Assn::Le(a0, a1) => {
+                write!(f, "({} ", if let Assn::Eq(..) = a { "=" } else { "<=" })?;
+                self.write_term(f, a0)?;
+                f.write_char(' ')?;
+                self.write_term(f, a1)?;
+                return f.write_char(')');
+            }
+            Assn::Forall(i, body) | Assn::Exists(i, body) => {
+                let q = if let Assn::Forall(..) = a {
+                    "forall"
+                } else {
+                    "exists"
+                };
+                write!(f, "({} (({} Int)) ", q, self.int_var(i))?;
+                self.write_formula(f, body)?;
+                return f.write_char(')');
+            }
+            Assn::Not(a) => ("not", vec![a]),
+            Assn::And(a0, a1) => ("and", vec![a0, a1]),
+            Assn::Or(a0, a1) => ("or", vec![a0, a1]),
+            Assn::Implies(a0, a1) => ("=>", vec![a0, a1]),
+        };
+        write!(f, "({}", op)?;
+        for arg in args {
+            f.write_char(' ')?;
+            self.write_formula(f, arg)?;
+        }
+        f.write_char(')')
+    }
+}
+
+/// 表明を SMT-LIB2 の論理式にします。
+pub fn formula(a: &Assn) -> String {
+    let names = Names::of([a], &BTreeSet::new());
+    let mut out = String::new();
+    names
+        .write_formula(&mut out, a)
+        .expect("writing to a String never fails");
+    out
+}
+
+/// 検証条件 `vcs` のそれぞれの妥当性を確かめる SMT-LIB2 のスクリプトを返します。
+///
+/// `vcs` に現れない変数も宣言したいときは `locations` に渡します。
+pub fn script(vcs: &[Assn], locations: &BTreeSet<VarName>) -> String {
+    let names = Names::of(vcs, locations);
+    let mut vars = BTreeSet::new();
+    let mut quantified = false;
+    for vc in vcs {
+        vars.extend(vc.free_vars());
+        quantified |= has_quantifier(vc);
+    }
+
+    let mut out = String::new();
+    let logic = if quantified { "NIA" } else { "QF_NIA" };
+    writeln!(out, "(set-logic {})", logic).unwrap();
+    for x in names.locations.values() {
+        writeln!(out, "(declare-const {} Int)", x).unwrap();
+    }
+    for i in &vars {
+        writeln!(out, "(declare-const {} Int)", names.int_var(i)).unwrap();
+    }
+    for vc in vcs {
+        writeln!(out, "(push 1)").unwrap();
+        writeln!(out, "; {}", vc).unwrap();
+        out.push_str("(assert (not ");
+        names.write_formula(&mut out, vc).unwrap();
+        out.push_str("))\n");
+        writeln!(out, "(check-sat)").unwrap();
+        writeln!(out, "(pop 1)").unwrap();
+    }
+    out.push_str("(exit)\n");
+    out
+}
+
+/// `{pre} c {post}` の検証条件を確かめる SMT-LIB2 のスクリプトを返します。
+/// `c` に現れるプログラム変数はすべて宣言します。
+pub fn triple_script(pre: &Assn, c: &ACom, post: &Assn) -> String {
+    let vcs = verification_conditions(pre, c, post);
+    let mut locations = BTreeSet::new();
+    collect_locations(&c.erase(), &mut locations);
+    format!(
+        "; {{{}}} {} {{{}}}\n{}",
+        pre,
+        c,
+        post,
+        script(&vcs, &locations)
+    )
+}
+
+fn has_quantifier(a: &Assn) -> bool {
+    match a {
+        Assn::T(_) | Assn::Eq(..) | Assn::Le(..) => false,
+        Assn::Not(a) => has_quantifier(a),
+        Assn::And(a0, a1) | Assn::Or(a0, a1) | Assn::Implies(a0, a1) => {
+            has_quantifier(a0) || has_quantifier(a1)
+        }
+        Assn::Forall(..) | Assn::Exists(..) => true,
+    }
+}
+
+/// 束縛されたものも含めて、現れる整数変数を集めます。
+fn collect_int_vars(a: &Assn, vars: &mut BTreeSet<IntVar>) {
+    match a {
+        Assn::T(_) => {}
+        Assn::Eq(a0, a1) | Assn::Le(a0, a1) => {
+            vars.extend(a0.free_vars());
+            vars.extend(a1.free_vars());
+        }
+        Assn::Not(a) => collect_int_vars(a, vars),
+        Assn::And(a0, a1) | Assn::Or(a0, a1) | Assn::Implies(a0, a1) => {
+            collect_int_vars(a0, vars);
+            collect_int_vars(a1, vars);
+        }
+        Assn::Forall(i, a) | Assn::Exists(i, a) => {
+            vars.insert(i.clone());
+            collect_int_vars(a, vars);
+        }
+    }
+}
+
+fn collect_locations(c: &Com, locations: &mut BTreeSet<VarName>) {
+    match c {
+        Com::Skip => {}
+        Com::Subst(x, a) => {
+            locations.insert(x.clone());
+            locations.extend(Aexpv::from(a.clone()).locations());
+        }
+        Com::Seq(c_0, c_1) => {
+            collect_locations(c_0, locations);
+            collect_locations(c_1, locations);
+        }
+        Com::If(b, c_0, c_1) => {
+            locations.extend(Assn::from(b.clone()).locations());
+            collect_locations(c_0, locations);
+            collect_locations(c_1, locations);
+        }
+        Com::While(b, c) => {
+            locations.extend(Assn::from(b.clone()).locations());
+            collect_locations(c, locations);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::imp::{
+        assn::{Aexpv, Assn},
+        parse_bexp, parse_com,
+        smtlib::{formula, script, triple_script},
+        vcgen::ACom,
+    };
+
+    fn assn(b: &str) -> Assn {
+        parse_bexp(b).unwrap().into()
+    }
+
+    #[test]
+    fn bexp_operators() {
+        assert_eq!(
+            "(or (and (<= X (+ Y 1)) (not (= X (- 3)))) false)",
+            formula(&assn("X <= Y + 1 and not X = -3 or false"))
+        );
+        assert_eq!("(= (* (- X Y) 2) 0)", formula(&assn("(X - Y) * 2 = 0")));
+    }
+
+    #[test]
+    fn quantifiers_and_names() {
+        // ∀X. X <= X ⇒ ∃j'. X = j'（左の X は整数変数、右の X はプログラム変数）
+        let a = Assn::forall(
+            "X",
+            Assn::implies(
+                Assn::Le(Aexpv::var("X"), Aexpv::Loc("X".into())),
+                Assn::exists("j'", Assn::Eq(Aexpv::var("X"), Aexpv::var("j'"))),
+            ),
+        );
+        assert_eq!(
+            "(forall ((|X'| Int)) (=> (<= |X'| X) (exists ((|j'| Int)) (= |X'| |j'|))))",
+            formula(&a)
+        );
+    }
+
+    #[test]
+    fn fresh_int_var_names() {
+        // 整数変数 X はプログラム変数 X と重なるが、X' も整数変数なので X'' にする
+        let a = Assn::forall(
+            "X",
+            Assn::Eq(
+                Aexpv::Add(Box::new(Aexpv::var("X")), Box::new(Aexpv::var("X'"))),
+                Aexpv::Loc("X".into()),
+            ),
+        );
+        assert_eq!("(forall ((|X''| Int)) (= (+ |X''| |X'|) X))", formula(&a));
+        let out = script(&[a], &BTreeSet::new());
+        assert!(out.contains("(declare-const X Int)\n(declare-const |X'| Int)\n"));
+    }
+
+    #[test]
+    fn reserved_and_builtin_names() {
+        // let は予約語なので囲み、div は Ints の記号なので別の名前にする
+        let a = Assn::exists(
+            "mod",
+            Assn::Eq(
+                Aexpv::Add(
+                    Box::new(Aexpv::Loc("let".into())),
+                    Box::new(Aexpv::var("mod")),
+                ),
+                Aexpv::Loc("div".into()),
+            ),
+        );
+        assert_eq!(
+            "(exists ((|mod'| Int)) (= (+ |let| |mod'|) |div'|))",
+            formula(&a)
+        );
+        assert!(script(&[a], &BTreeSet::new())
+            .contains("(declare-const |div'| Int)\n(declare-const |let| Int)\n"));
+    }
+
+    #[test]
+    fn script_for_triple() {
+        let c = ACom::annotate(
+            &parse_com("Y := 0; while 1 <= X do X := X - 1").unwrap(),
+            vec![assn("0 <= X")],
+        )
+        .unwrap();
+        assert_eq!(
+            "; {0 <= X} Y := 0; while 1 <= X inv {0 <= X} do X := X - 1 {X = 0}\n\
+             (set-logic QF_NIA)\n\
+             (declare-const X Int)\n\
+             (declare-const Y Int)\n\
+             (push 1)\n\
+             ; 0 <= X ⇒ 0 <= X\n\
+             (assert (not (=> (<= 0 X) (<= 0 X))))\n\
+             (check-sat)\n\
+             (pop 1)\n\
+             (push 1)\n\
+             ; 0 <= X ∧ 1 <= X ⇒ 0 <= X - 1\n\
+             (assert (not (=> (and (<= 0 X) (<= 1 X)) (<= 0 (- X 1)))))\n\
+             (check-sat)\n\
+             (pop 1)\n\
+             (push 1)\n\
+             ; 0 <= X ∧ ¬1 <= X ⇒ X = 0\n\
+             (assert (not (=> (and (<= 0 X) (not (<= 1 X))) (= X 0))))\n\
+             (check-sat)\n\
+             (pop 1)\n\
+             (exit)\n",
+            triple_script(&assn("0 <= X"), &c, &assn("X = 0"))
+        );
+    }
+
+    #[test]
+    fn declare_free_int_vars() {
+        let vc = Assn::Eq(Aexpv::Loc("X".into()), Aexpv::var("n"));
+        let out = script(&[vc], &BTreeSet::new());
+        assert!(out.contains("(declare-const X Int)\n(declare-const n Int)\n"));
+
+        let vc = Assn::forall("i", Assn::Le(Aexpv::var("i"), Aexpv::var("i")));
+        assert!(script(&[vc], &BTreeSet::new()).starts_with("(set-logic NIA)\n(push 1)\n"));
+    }
+}
@@ -0,0 +1,266 @@
+//! IMP の記号実行
+//!
+//! 変数の値を整数ではなく算術式で表した状態 `State<Aexp>` でコマンドを実行します。
+//! 状態に値のない変数 `X` は、実行を始めたときの `X` の値を表します。
+//!
+//! `if` と `while` の条件が初期値によって決まるときは両方の分岐に進み、
+//! 通った分岐の条件を経路条件として集めます。
+//! 条件が初期値によらず決まるときは、成り立つ側にだけ進みます。
+//! `while` は各経路で高々 `unroll` 回まで展開し、それ以上続く経路は打ち切ります。
+//!
+//! ```text
+//! while 1 <= X do X := X - 1 を 1 回まで展開すると
+//!
+//! not 1 <= X ⟹ {}
+//! 1 <= X and 1 <= X - 1 ⟹ {X ↦ X - 1} …
+//! 1 <= X and not 1 <= X - 1 ⟹ {X ↦ X - 1}
+//! ```
+
+use std::fmt;
+
+use crate::{
+    imp::{Aexp, Bexp, BexpImpl, Com, ImpError},
+    State, TryEvaluateRef,
+};
+
+/// 記号実行の経路
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    /// 経路条件。通った分岐の条件を順に並べたもので、すべて成り立つときにこの経路を通ります。
+    pub condition: Vec<Bexp>,
+    /// 経路の終わりでの記号的な状態
+    pub state: State<Aexp>,
+    /// `while` の展開回数の上限で打ち切ったかどうか
+    pub truncated: bool,
+}
+
+impl Path {
+    fn new() -> Path {
+        Path {
+            condition: vec![],
+            state: State::init(),
+            truncated: false,
+        }
+    }
+
+    /// 経路条件を連言にしたブール式を返します。
+    pub fn condition(&self) -> Bexp {
+        self.condition
+            .iter()
+            .cloned()
+            .reduce(Bexp::and)
+            .unwrap_or_else(|| Bexp::truth(true))
+    }
+
+    /// 初期状態 `state` でこの経路を通るなら、経路の終わりでの状態を返します。
+    pub fn run(&self, state: &State) -> Result<Option<State>, ImpError> {
+        for b in &self.condition {
+            if !bool::from(b.try_evaluate_ref(state)?) {
+                return Ok(None);
+            }
+        }
+
+        let mut result = state.clone();
+        for (x, a) in &self.state.0 {
+            if let Some(a) = a {
+                let n = a.try_evaluate_ref(state)?;
+                result = result.update_variable(x, n);
+            }
+        }
+        Ok(Some(result))
+    }
+}
+
+/// `b ⟹ σ` の形で出力します。打ち切った経路には ` …` を付けます。
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ⟹ {}", self.condition(), self.state)?;
+        if self.truncated {
+            f.write_str(" …")?;
+        }
+        Ok(())
+    }
+}
+
+/// `c` を記号実行し、`while` を高々 `unroll` 回まで展開したときの経路をすべて返します。
+///
+/// 経路は `if` や `while` の条件が成り立つ側を先にして並べます。
+pub fn explore(c: &Com, unroll: usize) -> Vec<Path> {
+    exec(c, Path::new(), unroll)
+}
+
+fn exec(c: &Com, mut path: Path, unroll: usize) -> Vec<Path> {
+    if path.truncated {
+        return vec![path];
+    }
+    match c {
+        Com::Skip => vec![path],
+        Com::Subst(x, a) => {
+            let a = a.substitute(&path.state);
+            path.state = path.state.update_variable(x, a);
+            vec![path]
+        }
+        Com::Seq(c_0, c_1) => exec(c_0, path, unroll)
+            .into_iter()
+            .flat_map(|path| exec(c_1, path, unroll))
+            .collect(),
+        Com::If(b, c_0, c_1) => branch(path, b)
+            .into_iter()
+            .flat_map(|(path, t)| exec(if t { c_0 } else { c_1 }, path, unroll))
+            .collect(),
+        Com::While(b, c) => {
+            let mut paths = vec![];
+            let mut running = vec![path];
+            for k in 0..=unroll {
+                let mut next = vec![];
+                for path in running {
+                    if path.truncated {
+                        paths.push(path);
+                        continue;
+                    }
+                    for (mut path, t) in branch(path, b) {
+                        if !t {
+                            paths.push(path);
+                        } else if k == unroll {
+                            path.truncated = true;
+                            paths.push(path);
+                        } else {
+                            next.extend(exec(c, path, unroll));
+                        }
+                    }
+                }
+                running = next;
+            }
+            paths
+        }
+    }
+}
+
+/// 条件 `b` で経路を分け、`b` が成り立つかどうかと組にして返します。
+fn branch(path: Path, b: &Bexp) -> Vec<(Path, bool)> {
+    let b = b.substitute(&path.state);
+    // 変数を含まない条件は評価できる
+    if let Ok(t) = b.try_evaluate_ref(&State::init()) {
+        return vec![(path, t.into())];
+    }
+
+    let mut then = path.clone();
+    then.condition.push(b.clone());
+    let mut otherwise = path;
+    otherwise.condition.push(Bexp::not(b));
+    vec![(then, true), (otherwise, false)]
+}
+
+impl Aexp {
+    /// 式の中の変数を記号的な状態 `state` での値に置き換えます。
+    /// `state` に値のない変数はそのまま残します。
+    pub fn substitute(&self, state: &State<Aexp>) -> Aexp {
+        match self {
+            Aexp::N(_) => self.clone(),
+            Aexp::Loc(x) => state.get(x).clone().unwrap_or_else(|| self.clone()),
+            Aexp::Add(a_0, a_1) => Aexp::Add(
+                Box::new(a_0.substitute(state)),
+                Box::new(a_1.substitute(state)),
+            ),
+            Aexp::Sub(a_0, a_1) => Aexp::Sub(
+                Box::new(a_0.substitute(state)),
+                Box::new(a_1.substitute(state)),
+            ),
+            Aexp::Mul(a_0, a_1) => Aexp::Mul(
+                Box::new(a_0.substitute(state)),
+                Box::new(a_1.substitute(state)),
+            ),
+        }
+    }
+}
+
+impl Bexp {
+    /// 式の中の変数を記号的な状態 `state` での値に置き換えます。
+    /// `state` に値のない変数はそのまま残します。
+    pub fn substitute(&self, state: &State<Aexp>) -> Bexp {
+        substitute(&self.bexp, state)
+    }
+}
+
+fn substitute(b: &BexpImpl, state: &State<Aexp>) -> Bexp {
+    match b {
+        BexpImpl::T(t) => Bexp::truth((*t).into()),
+        BexpImpl::Eq(a_0, a_1) => Bexp::eq(a_0.substitute(state), a_1.substitute(state)),
+        BexpImpl::Le(a_0, a_1) => Bexp::le(a_0.substitute(state), a_1.substitute(state)),
+        BexpImpl::Not(b) => Bexp::not(substitute(b, state)),
+        BexpImpl::And(b_0, b_1) => Bexp::and(substitute(b_0, state), substitute(b_1, state)),
+        BexpImpl::Or(b_0, b_1) => Bexp::or(substitute(b_0, state), substitute(b_1, state)),
+        BexpImpl::Dummy => panic!(), // 短絡評価のテスト用
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{parse_com, symbolic::explore},
+        Execute, State,
+    };
+
+    fn strings(c: &str, unroll: usize) -> Vec<String> {
+        explore(&parse_com(c).unwrap(), unroll)
+            .iter()
+            .map(|path| path.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn fork_at_if() {
+        assert_eq!(
+            vec![
+                "X <= 0 ⟹ {Y ↦ 0 - X}".to_string(),
+                "not X <= 0 ⟹ {Y ↦ X}".to_string(),
+            ],
+            strings("if X <= 0 then Y := 0 - X else Y := X", 0)
+        );
+    }
+
+    #[test]
+    fn unroll_while() {
+        assert_eq!(
+            vec![
+                "not 1 <= X ⟹ {}".to_string(),
+                "1 <= X and not 1 <= X - 1 ⟹ {X ↦ X - 1}".to_string(),
+                "1 <= X and 1 <= X - 1 and 1 <= X - 1 - 1 ⟹ {X ↦ X - 1 - 1} …".to_string(),
+                "1 <= X and 1 <= X - 1 and not 1 <= X - 1 - 1 ⟹ {X ↦ X - 1 - 1}".to_string(),
+            ],
+            strings("while 1 <= X do X := X - 1", 2)
+        );
+    }
+
+    #[test]
+    fn ground_conditions_do_not_fork() {
+        assert_eq!(
+            vec!["true ⟹ {X ↦ 3 - 1 - 1 - 1, Y ↦ 0 + 3 + (3 - 1) + (3 - 1 - 1)}".to_string()],
+            strings(
+                "X := 3; Y := 0; while 1 <= X do { Y := Y + X; X := X - 1 }",
+                5
+            )
+        );
+    }
+
+    #[test]
+    fn paths_agree_with_execution() {
+        let c = parse_com(
+            "Z := 0; if X <= 0 then X := 0 - X else skip; \
+             while 1 <= X do { if Y <= X then Z := Z + Y else Z := Z - 1; X := X - 1 }",
+        )
+        .unwrap();
+        let paths = explore(&c, 4);
+        for x in -4..=4 {
+            for y in -2..=2 {
+                let state = State::from(&[("X", x.into()), ("Y", y.into())]);
+                let finals: Vec<_> = paths
+                    .iter()
+                    .filter_map(|path| path.run(&state).unwrap())
+                    .collect();
+                // 経路条件は互いに排他的なので、ちょうど 1 つの経路を通る
+                assert_eq!(vec![c.execute(state.clone()).1], finals, "{}", state);
+            }
+        }
+    }
+}
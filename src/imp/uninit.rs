@@ -0,0 +1,212 @@
+//! 未初期化変数の検出
+//!
+//! 各地点で「どの経路を通っても代入済み」の変数の集合を前向きに計算し、
+//! その集合にない変数を読む箇所を報告します。
+//!
+//! ```text
+//! DA(skip, D)                     = D
+//! DA(X := a, D)                   = D ∪ {X}
+//! DA(c_0; c_1, D)                 = DA(c_1, DA(c_0, D))
+//! DA(if b then c_0 else c_1, D)   = DA(c_0, D) ∩ DA(c_1, D)
+//! DA(while b do c, D)             = D
+//! ```
+//!
+//! `while` の本体は 1 回も実行されないことがあるので、ループの後で代入済みといえるのはループの前と同じ変数です。
+//! また本体は代入済みの変数を減らさないので、ループの先頭での集合もループの前と同じです。
+//! `and`/`or` の右辺は短絡評価で読まれないこともありますが、読まれうるので報告します。
+
+use std::{collections::BTreeSet, fmt};
+
+use crate::{
+    imp::{Aexp, Bexp, BexpImpl, Com},
+    VarName,
+};
+
+/// 変数を読む箇所
+#[derive(Debug, Clone, PartialEq)]
+pub enum Site {
+    /// 代入 `X := a` の右辺
+    Subst(VarName, Aexp),
+    /// `if b then ...` の条件
+    If(Bexp),
+    /// `while b do ...` の条件
+    While(Bexp),
+}
+
+impl fmt::Display for Site {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Site::Subst(x, a) => write!(f, "{} := {}", x, a),
+            Site::If(b) => write!(f, "if {}", b),
+            Site::While(b) => write!(f, "while {}", b),
+        }
+    }
+}
+
+/// 代入される前に読まれうる変数
+#[derive(Debug, Clone, PartialEq)]
+pub struct UninitRead {
+    /// 読まれる変数
+    pub var: VarName,
+    /// 読まれる箇所
+    pub site: Site,
+}
+
+impl fmt::Display for UninitRead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} may be read before assignment in `{}`",
+            self.var, self.site
+        )
+    }
+}
+
+/// `inputs` の変数は代入済みとして `c` を調べ、代入される前に読まれうる変数をプログラムの出現順に返します。
+pub fn check(c: &Com, inputs: &[VarName]) -> Vec<UninitRead> {
+    let mut reads = vec![];
+    analyze(c, inputs.iter().cloned().collect(), &mut reads);
+    reads
+}
+
+/// `assigned` の変数が代入済みの状態から `c` を実行した後で、代入済みの変数を返します。
+fn analyze(c: &Com, assigned: BTreeSet<VarName>, reads: &mut Vec<UninitRead>) -> BTreeSet<VarName> {
+    match c {
+        Com::Skip => assigned,
+        Com::Subst(x, a) => {
+            report(
+                &aexp_vars(a),
+                &assigned,
+                Site::Subst(x.clone(), a.clone()),
+                reads,
+            );
+            let mut assigned = assigned;
+            assigned.insert(x.clone());
+            assigned
+        }
+        Com::Seq(c_0, c_1) => {
+            let assigned = analyze(c_0, assigned, reads);
+            analyze(c_1, assigned, reads)
+        }
+        Com::If(b, c_0, c_1) => {
+            report(&bexp_vars(b), &assigned, Site::If(b.clone()), reads);
+            let assigned_0 = analyze(c_0, assigned.clone(), reads);
+            let assigned_1 = analyze(c_1, assigned, reads);
+            assigned_0.intersection(&assigned_1).cloned().collect()
+        }
+        Com::While(b, c) => {
+            report(&bexp_vars(b), &assigned, Site::While(b.clone()), reads);
+            analyze(c, assigned.clone(), reads);
+            assigned
+        }
+    }
+}
+
+fn report(vars: &[VarName], assigned: &BTreeSet<VarName>, site: Site, reads: &mut Vec<UninitRead>) {
+    let mut seen = BTreeSet::new();
+    for x in vars {
+        if !assigned.contains(x) && seen.insert(x) {
+            reads.push(UninitRead {
+                var: x.clone(),
+                site: site.clone(),
+            });
+        }
+    }
+}
+
+/// 式に現れる変数を左から順に返します。
+fn aexp_vars(a: &Aexp) -> Vec<VarName> {
+    match a {
+        Aexp::N(_) => vec![],
+        Aexp::Loc(x) => vec![x.clone()],
+        Aexp::Add(a_0, a_1) | Aexp::Sub(a_0, a_1) | Aexp::Mul(a_0, a_1) => {
+            let mut vars = aexp_vars(a_0);
+            vars.extend(aexp_vars(a_1));
+            vars
+        }
+    }
+}
+
+fn bexp_vars(b: &Bexp) -> Vec<VarName> {
+    fn vars(b: &BexpImpl) -> Vec<VarName> {
+        match b {
+            BexpImpl::T(_) | BexpImpl::Dummy => vec![],
+            BexpImpl::Eq(a_0, a_1) | BexpImpl::Le(a_0, a_1) => {
+                let mut vars = aexp_vars(a_0);
+                vars.extend(aexp_vars(a_1));
+                vars
+            }
+            BexpImpl::Not(b) => vars(b),
+            BexpImpl::And(b_0, b_1) | BexpImpl::Or(b_0, b_1) => {
+                let mut v = vars(b_0);
+                v.extend(vars(b_1));
+                v
+            }
+        }
+    }
+    vars(&b.bexp)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{parse_com, uninit::check, ImpError},
+        State, TryExecute, VarName,
+    };
+
+    fn strings(c: &str, inputs: &[&str]) -> Vec<String> {
+        let inputs: Vec<VarName> = inputs.iter().map(|&x| x.into()).collect();
+        check(&parse_com(c).unwrap(), &inputs)
+            .iter()
+            .map(|read| read.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn straight_line() {
+        assert!(strings("Y := X + 1; Z := Y * X", &["X"]).is_empty());
+        assert_eq!(
+            vec![
+                "Y may be read before assignment in `X := Y + Z * Y`",
+                "Z may be read before assignment in `X := Y + Z * Y`",
+            ],
+            strings("X := Y + Z * Y; Y := X", &[])
+        );
+    }
+
+    #[test]
+    fn branches_and_loops() {
+        // 一方の分岐でしか代入しない
+        assert_eq!(
+            vec!["Y may be read before assignment in `Z := Y`"],
+            strings("if X <= 0 then Y := 1 else skip; Z := Y", &["X"])
+        );
+        assert!(strings("if X <= 0 then Y := 1 else Y := 2; Z := Y", &["X"]).is_empty());
+
+        // ループの本体は実行されないことがある
+        assert_eq!(
+            vec![
+                "S may be read before assignment in `S := S + X`",
+                "S may be read before assignment in `Y := S`",
+            ],
+            strings("while 1 <= X do { S := S + X; X := X - 1 }; Y := S", &["X"])
+        );
+        // 条件で読む変数も報告する
+        assert_eq!(
+            vec![
+                "N may be read before assignment in `while 1 <= N`",
+                "N may be read before assignment in `N := N - 1`",
+            ],
+            strings("while 1 <= N do N := N - 1", &[])
+        );
+    }
+
+    #[test]
+    fn reported_reads_fail_at_runtime() {
+        let c = parse_com("if X <= 0 then Y := 1 else skip; Z := Y").unwrap();
+        assert_eq!(
+            Err(ImpError::UndefinedVariable("Y".into())),
+            c.try_execute(State::from(&[("X", 1.into())]))
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! 最弱事前条件と検証条件の生成
+//!
+//! `while` に不変条件を注釈したコマンド [`ACom`] について、最弱事前条件 `wp(c, B)` と、
+//! `{A} c {B}` を示すのに必要な検証条件を計算します。
+//!
+//! ```text
+//! wp(skip, B)                     = B
+//! wp(X := a, B)                   = B[a/X]
+//! wp(c_0; c_1, B)                 = wp(c_0, wp(c_1, B))
+//! wp(if b then c_0 else c_1, B)   = (b ⇒ wp(c_0, B)) ∧ (¬b ⇒ wp(c_1, B))
+//! wp(while b inv {I} do c, B)     = I
+//!
+//! vc(while b inv {I} do c, B)     = {I ∧ b ⇒ wp(c, I), I ∧ ¬b ⇒ B} ∪ vc(c, I)
+//! ```
+//!
+//! `while` の `wp` は注釈した不変条件で代用するので、本当の最弱事前条件より強くなることがあります。
+//! 検証条件がすべて妥当なら `{A} c {B}` は部分正当性の意味で成り立ちます。
+
+use std::fmt::{self, Write};
+
+use crate::{
+    imp::{assn::Assn, Aexp, Bexp, Com},
+    VarName,
+};
+
+/// `while` に不変条件を注釈したコマンド
+#[derive(Debug, Clone, PartialEq)]
+pub enum ACom {
+    /// 基礎コマンド
+    Skip,
+    /// 代入 `X := a`
+    Subst(VarName, Aexp),
+    /// 逐次実行 `c_0 ; c_1`
+    Seq(Box<ACom>, Box<ACom>),
+    /// 条件分岐 `if b then c_0 else c_1`
+    If(Bexp, Box<ACom>, Box<ACom>),
+    /// 不変条件 `I` を持つ whileループ `while b inv {I} do c`
+    While(Bexp, Assn, Box<ACom>),
+}
+
+impl ACom {
+    /// `com` の `while` に、出現順に `invariants` の不変条件を注釈します。
+    /// `while` の個数と不変条件の個数が合わなければ `None` を返します。
+    pub fn annotate(com: &Com, invariants: Vec<Assn>) -> Option<ACom> {
+        let mut invariants = invariants.into_iter();
+        let acom = ACom::annotate_with(com, &mut invariants)?;
+        invariants.next().is_none().then_some(acom)
+    }
+
+    fn annotate_with(com: &Com, invariants: &mut impl Iterator<Item = Assn>) -> Option<ACom> {
+        Some(match com {
+            Com::Skip => ACom::Skip,
+            Com::Subst(x, a) => ACom::Subst(x.clone(), a.clone()),
+            Com::Seq(c_0, c_1) => ACom::Seq(
+                Box::new(ACom::annotate_with(c_0, invariants)?),
+                Box::new(ACom::annotate_with(c_1, invariants)?),
+            ),
+            Com::If(b, c_0, c_1) => ACom::If(
+                b.clone(),
+                Box::new(ACom::annotate_with(c_0, invariants)?),
+                Box::new(ACom::annotate_with(c_1, invariants)?),
+            ),
+            Com::While(b, c) => {
+                let invariant = invariants.next()?;
+                ACom::While(
+                    b.clone(),
+                    invariant,
+                    Box::new(ACom::annotate_with(c, invariants)?),
+                )
+            }
+        })
+    }
+
+    /// 注釈を取り除いたコマンドを返します。
+    pub fn erase(&self) -> Com {
+        match self {
+            ACom::Skip => Com::Skip,
+            ACom::Subst(x, a) => Com::Subst(x.clone(), a.clone()),
+            ACom::Seq(c_0, c_1) => Com::Seq(Box::new(c_0.erase()), Box::new(c_1.erase())),
+            ACom::If(b, c_0, c_1) => {
+                Com::If(b.clone(), Box::new(c_0.erase()), Box::new(c_1.erase()))
+            }
+            ACom::While(b, _, c) => Com::While(b.clone(), Box::new(c.erase())),
+        }
+    }
+
+    /// 事後条件 `post` に対する最弱事前条件 `wp(c, B)` を返します。
+    pub fn wp(&self, post: &Assn) -> Assn {
+        match self {
+            ACom::Skip => post.clone(),
+            ACom::Subst(x, a) => post.subst(x, a),
+            ACom::Seq(c_0, c_1) => c_0.wp(&c_1.wp(post)),
+            ACom::If(b, c_0, c_1) => {
+                let b = Assn::from(b.clone());
+                Assn::and(
+                    Assn::implies(b.clone(), c_0.wp(post)),
+                    Assn::implies(Assn::not(b), c_1.wp(post)),
+                )
+            }
+            ACom::While(_, invariant, _) => invariant.clone(),
+        }
+    }
+
+    /// 事後条件 `post` に対して、`{wp(c, B)} c {B}` を示すのに必要な検証条件を返します。
+    pub fn vc(&self, post: &Assn) -> Vec<Assn> {
+        let mut vcs = Vec::new();
+        self.vc_into(post, &mut vcs);
+        vcs
+    }
+
+    fn vc_into(&self, post: &Assn, vcs: &mut Vec<Assn>) {
+        match self {
+            ACom::Skip | ACom::Subst(..) => {}
+            ACom::Seq(c_0, c_1) => {
+                c_0.vc_into(&c_1.wp(post), vcs);
+                c_1.vc_into(post, vcs);
+            }
+            ACom::If(_, c_0, c_1) => {
+                c_0.vc_into(post, vcs);
+                c_1.vc_into(post, vcs);
+            }
+            ACom::While(b, invariant, c) => {
+                let b = Assn::from(b.clone());
+                // 本体が不変条件を保つ
+                vcs.push(Assn::implies(
+                    Assn::and(invariant.clone(), b.clone()),
+                    c.wp(invariant),
+                ));
+                // ループを抜けたら事後条件が成り立つ
+                vcs.push(Assn::implies(
+                    Assn::and(invariant.clone(), Assn::not(b)),
+                    post.clone(),
+                ));
+                c.vc_into(invariant, vcs);
+            }
+        }
+    }
+}
+
+/// `{pre} c {post}` を示すのに必要な検証条件 `pre ⇒ wp(c, post)` と `vc(c, post)` を返します。
+pub fn verification_conditions(pre: &Assn, c: &ACom, post: &Assn) -> Vec<Assn> {
+    let mut vcs = vec![Assn::implies(pre.clone(), c.wp(post))];
+    c.vc_into(post, &mut vcs);
+    vcs
+}
+
+/// 1 行で出力します。
+fn write_acom(f: &mut impl Write, c: &ACom) -> fmt::Result {
+    match c {
+        ACom::Skip => f.write_str("skip"),
+        ACom::Subst(x, a) => write!(f, "{} := {}", x, a),
+        ACom::Seq(c_0, c_1) => {
+            write_simple(f, c_0)?;
+            f.write_str("; ")?;
+            write_acom(f, c_1)
+        }
+        ACom::If(b, c_0, c_1) => {
+            write!(f, "if {} then ", b)?;
+            write_simple(f, c_0)?;
+            f.write_str(" else ")?;
+            write_simple(f, c_1)
+        }
+        ACom::While(b, invariant, c) => {
+            write!(f, "while {} inv {{{}}} do ", b, invariant)?;
+            write_simple(f, c)
+        }
+    }
+}
+
+/// `;` を含まないコマンドとして 1 行で出力します。
+fn write_simple(f: &mut impl Write, c: &ACom) -> fmt::Result {
+    if let ACom::Seq(..) = c {
+        f.write_str("{ ")?;
+        write_acom(f, c)?;
+        f.write_str(" }")
+    } else {
+        write_acom(f, c)
+    }
+}
+
+impl fmt::Display for ACom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_acom(f, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        imp::{
+            assn::{Aexpv, Assn, Interpretation},
+            parse_bexp, parse_com,
+            vcgen::{verification_conditions, ACom},
+        },
+        State,
+    };
+
+    fn assn(b: &str) -> Assn {
+        parse_bexp(b).unwrap().into()
+    }
+
+    fn strings(vcs: &[Assn]) -> Vec<String> {
+        vcs.iter().map(Assn::to_string).collect()
+    }
+
+    #[test]
+    fn annotate_and_erase() {
+        let com = parse_com("while X <= 3 do { Y := 0; while Y <= X do Y := Y + 1 }").unwrap();
+        let acom = ACom::annotate(&com, vec![assn("true"), assn("0 <= Y")]).unwrap();
+        assert_eq!(
+            "while X <= 3 inv {true} do { Y := 0; while Y <= X inv {0 <= Y} do Y := Y + 1 }",
+            acom.to_string()
+        );
+        assert_eq!(com, acom.erase());
+
+        assert_eq!(None, ACom::annotate(&com, vec![assn("true")]));
+        assert_eq!(None, ACom::annotate(&com, vec![assn("true"); 3]));
+    }
+
+    #[test]
+    fn wp() {
+        let c = ACom::annotate(&parse_com("X := X + 1; Y := X * 2").unwrap(), vec![]).unwrap();
+        assert_eq!("(X + 1) * 2 = 4", c.wp(&assn("Y = 4")).to_string());
+
+        let c = ACom::annotate(
+            &parse_com("if X <= Y then Z := Y else Z := X").unwrap(),
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(
+            "(X <= Y ⇒ X <= Y ∧ Y <= Y) ∧ (¬X <= Y ⇒ X <= X ∧ Y <= X)",
+            c.wp(&assn("X <= Z and Y <= Z")).to_string()
+        );
+    }
+
+    #[test]
+    fn factorial_vcs() {
+        // {X = n} Y := 1; while 1 <= X inv {I} do { Y := Y * X; X := X - 1 } {Y = 120}
+        // 階乗は表明で書けないので、不変条件 I は X の値ごとに Y の値を列挙する
+        let com = parse_com("Y := 1; while 1 <= X do { Y := Y * X; X := X - 1 }").unwrap();
+        let invariant = assn(
+            "(X = 5 and Y = 1) or (X = 4 and Y = 5) or (X = 3 and Y = 20) \
+             or (X = 2 and Y = 60) or (X = 1 and Y = 120) or (X = 0 and Y = 120)",
+        );
+        let c = ACom::annotate(&com, vec![invariant]).unwrap();
+        let pre = Assn::Eq(Aexpv::Loc("X".into()), Aexpv::var("n"));
+        let vcs = verification_conditions(&pre, &c, &assn("Y = 120"));
+        assert_eq!(3, vcs.len());
+
+        // n = 5 ならどの状態でも検証条件が成り立つ
+        let interp = Interpretation::default().with(&"n".into(), 5.into());
+        for x in -2..=6 {
+            for y in [0, 1, 5, 20, 60, 120] {
+                let state = State::from(&[("X", x.into()), ("Y", y.into())]);
+                for vc in &vcs {
+                    assert_eq!(Ok(true), vc.holds(&state, &interp), "{} at {}", vc, state);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn while_vcs() {
+        let c = ACom::annotate(
+            &parse_com("while 1 <= X do X := X - 1").unwrap(),
+            vec![assn("0 <= X")],
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                "0 <= X ⇒ 0 <= X",
+                "0 <= X ∧ 1 <= X ⇒ 0 <= X - 1",
+                "0 <= X ∧ ¬1 <= X ⇒ X = 0",
+            ],
+            strings(&verification_conditions(
+                &assn("0 <= X"),
+                &c,
+                &assn("X = 0")
+            ))
+        );
+    }
+}
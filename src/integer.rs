@@ -0,0 +1,273 @@
+//! 多倍長整数
+//!
+//! 教科書の整数全体 ℤ を表すための、桁数に上限のない整数です。
+//! 絶対値を 2^32 進法の桁の列（下位の桁から順）で持ちます。
+
+use std::{cmp::Ordering, fmt};
+
+use crate::Number;
+
+/// 整数 ℤ
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Integer {
+    /// 負なら `true`（0 のときは常に `false`）
+    negative: bool,
+    /// 絶対値の各桁（上位の 0 は持たない）
+    magnitude: Vec<u32>,
+}
+
+impl Integer {
+    /// 0
+    pub fn zero() -> Integer {
+        Integer {
+            negative: false,
+            magnitude: Vec::new(),
+        }
+    }
+
+    /// 0 かどうかを返します。
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    /// `Number` の範囲に収まれば変換して返します。
+    pub fn to_number(&self) -> Option<Number> {
+        let abs = match self.magnitude.as_slice() {
+            [] => 0,
+            [d] => *d as i64,
+            _ => return None,
+        };
+        let n = if self.negative { -abs } else { abs };
+        i32::try_from(n).ok().map(Number::from)
+    }
+
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> Integer {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        Integer {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+}
+
+/// 絶対値どうしを比較します。
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &d) in long.iter().enumerate() {
+        let sum = d as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+/// `a - b` を計算します。`a >= b` でなければなりません。
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &d) in a.iter().enumerate() {
+        let mut diff = d as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = if diff < 0 {
+            diff += 1 << 32;
+            1
+        } else {
+            0
+        };
+        result.push(diff as u32);
+    }
+    result
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = x as u64 * y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    result
+}
+
+impl From<i64> for Integer {
+    fn from(n: i64) -> Self {
+        let abs = n.unsigned_abs();
+        Integer::new(n < 0, vec![abs as u32, (abs >> 32) as u32])
+    }
+}
+
+impl From<i32> for Integer {
+    fn from(n: i32) -> Self {
+        Integer::from(n as i64)
+    }
+}
+
+impl From<Number> for Integer {
+    fn from(n: Number) -> Self {
+        Integer::from(n.0)
+    }
+}
+
+impl std::ops::Neg for Integer {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        Integer::new(!self.negative, self.magnitude)
+    }
+}
+
+impl std::ops::Add for Integer {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        if self.negative == rhs.negative {
+            return Integer::new(
+                self.negative,
+                add_magnitude(&self.magnitude, &rhs.magnitude),
+            );
+        }
+        match cmp_magnitude(&self.magnitude, &rhs.magnitude) {
+            Ordering::Less => Integer::new(
+                rhs.negative,
+                sub_magnitude(&rhs.magnitude, &self.magnitude),
+            ),
+            _ => Integer::new(
+                self.negative,
+                sub_magnitude(&self.magnitude, &rhs.magnitude),
+            ),
+        }
+    }
+}
+
+impl std::ops::Sub for Integer {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        self + -rhs
+    }
+}
+
+impl std::ops::Mul for Integer {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        Integer::new(
+            self.negative != rhs.negative,
+            mul_magnitude(&self.magnitude, &rhs.magnitude),
+        )
+    }
+}
+
+impl Ord for Integer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for Integer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Integer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // 10^9 で割った余りを下の桁から集める
+        const BASE: u64 = 1_000_000_000;
+        let mut magnitude = self.magnitude.clone();
+        let mut chunks = Vec::new();
+        while !magnitude.is_empty() {
+            let mut rem = 0u64;
+            for d in magnitude.iter_mut().rev() {
+                let cur = (rem << 32) | *d as u64;
+                *d = (cur / BASE) as u32;
+                rem = cur % BASE;
+            }
+            chunks.push(rem);
+            while magnitude.last() == Some(&0) {
+                magnitude.pop();
+            }
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Integer, Number};
+
+    fn int(n: i64) -> Integer {
+        Integer::from(n)
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(int(7), int(3) + int(4));
+        assert_eq!(int(-1), int(3) - int(4));
+        assert_eq!(int(0), int(-4) + int(4));
+        assert_eq!(int(-12), int(3) * int(-4));
+        assert_eq!(int(1 << 32), int(u32::MAX as i64) + int(1));
+        assert_eq!(int(u32::MAX as i64), int(1 << 32) - int(1));
+        assert_eq!(int(-(1 << 40)), int(1 << 20) * int(-(1 << 20)));
+    }
+
+    #[test]
+    fn ordering() {
+        assert!(int(-5) < int(-4));
+        assert!(int(-1) < int(0));
+        assert!(int(1 << 40) > int(1 << 33));
+        assert!(int(-(1 << 40)) < int(-(1 << 33)));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("0", int(0).to_string());
+        assert_eq!("-42", int(-42).to_string());
+        assert_eq!("9223372036854775807", int(i64::MAX).to_string());
+
+        // 30! = 265252859812191058636308480000000
+        let mut n = int(1);
+        for i in 1..=30 {
+            n = n * int(i);
+        }
+        assert_eq!("265252859812191058636308480000000", n.to_string());
+    }
+
+    #[test]
+    fn to_number() {
+        assert_eq!(Some(Number(i32::MIN)), int(i32::MIN as i64).to_number());
+        assert_eq!(Some(Number(i32::MAX)), int(i32::MAX as i64).to_number());
+        assert_eq!(None, int(i32::MAX as i64 + 1).to_number());
+        assert_eq!(None, int(1 << 40).to_number());
+    }
+}
@@ -7,24 +7,74 @@ use std::{collections::HashMap, fmt};
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Number(i32);
 
+// ビルドプロファイルによらず、オーバーフローしたら panic します。
+// 他の方針で計算するときは arith モジュールを使います。
+
 impl std::ops::Add for Number {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
-        (self.0 + rhs.0).into()
+        self.checked_add(rhs).expect("attempt to add with overflow")
     }
 }
 
 impl std::ops::Sub for Number {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self::Output {
-        (self.0 - rhs.0).into()
+        self.checked_sub(rhs).expect("attempt to subtract with overflow")
     }
 }
 
 impl std::ops::Mul for Number {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self::Output {
-        (self.0 * rhs.0).into()
+        self.checked_mul(rhs).expect("attempt to multiply with overflow")
+    }
+}
+
+impl Number {
+    /// 加算します。オーバーフローした場合は `None` を返します。
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Number)
+    }
+
+    /// 減算します。オーバーフローした場合は `None` を返します。
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Number)
+    }
+
+    /// 乗算します。オーバーフローした場合は `None` を返します。
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        self.0.checked_mul(rhs.0).map(Number)
+    }
+
+    /// 加算します。オーバーフローした場合は 2^32 を法として折り返します。
+    pub fn wrapping_add(self, rhs: Self) -> Self {
+        Number(self.0.wrapping_add(rhs.0))
+    }
+
+    /// 減算します。オーバーフローした場合は 2^32 を法として折り返します。
+    pub fn wrapping_sub(self, rhs: Self) -> Self {
+        Number(self.0.wrapping_sub(rhs.0))
+    }
+
+    /// 乗算します。オーバーフローした場合は 2^32 を法として折り返します。
+    pub fn wrapping_mul(self, rhs: Self) -> Self {
+        Number(self.0.wrapping_mul(rhs.0))
+    }
+
+    /// 加算します。オーバーフローした場合は最大値または最小値に飽和させます。
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        Number(self.0.saturating_add(rhs.0))
+    }
+
+    /// 減算します。オーバーフローした場合は最大値または最小値に飽和させます。
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        Number(self.0.saturating_sub(rhs.0))
+    }
+
+    /// 乗算します。オーバーフローした場合は最大値または最小値に飽和させます。
+    pub fn saturating_mul(self, rhs: Self) -> Self {
+        Number(self.0.saturating_mul(rhs.0))
     }
 }
 
@@ -46,6 +96,12 @@ impl From<i32> for Number {
     }
 }
 
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 /// 真偽値
 /// ```text
 /// Truth ::= "true" | "false"
@@ -84,11 +140,17 @@ impl From<Truth> for bool {
     }
 }
 
+impl fmt::Display for Truth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 /// 変数
 /// ```text
 /// VarName ::= 変数（X,Y,Z,...）
 /// ```
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct VarName(String);
 
 impl From<&str> for VarName {
@@ -110,38 +172,67 @@ impl fmt::Display for VarName {
 }
 
 /// 状態
+///
+/// 変数の値の型 `V` は既定では [`Number`] で、
+/// [`arith::Unbounded`] で計算するときは [`Integer`] です。
 #[derive(Debug, Clone, PartialEq)]
-pub struct State(HashMap<VarName, Option<Number>>);
+pub struct State<V = Number>(HashMap<VarName, Option<V>>);
 
-impl State {
+impl<V: Clone> State<V> {
     /// 初期状態を生成します。
-    pub fn init() -> State {
+    pub fn init() -> State<V> {
         State(HashMap::new())
     }
 
     /// 変数名と値の組のスライスから状態を生成します。
-    pub fn from(defs: &[(&str, Number)]) -> State {
+    pub fn from(defs: &[(&str, V)]) -> State<V> {
         let mut vars = HashMap::new();
         for def in defs {
-            vars.insert(VarName::from(def.0), Some(def.1));
+            vars.insert(VarName::from(def.0), Some(def.1.clone()));
         }
         State(vars)
     }
 
     /// この状態での変数 `var` の値を返します。
-    fn get(&self, var: &VarName) -> &Option<Number> {
+    fn get(&self, var: &VarName) -> &Option<V> {
         self.0.get(var).unwrap_or(&None)
     }
 
     /// 変数 var の値を value に置き換えた状態を生成します。
-    fn update_variable(mut self, var: &VarName, value: Number) -> Self {
+    fn update_variable(mut self, var: &VarName, value: V) -> Self {
+        self.assign(var, value);
+        self
+    }
+
+    /// 変数 var の値を value に置き換えます。
+    fn assign(&mut self, var: &VarName, value: V) {
         let vars = &mut self.0;
-        if let Some(v) = vars.get_mut(&var) {
+        if let Some(v) = vars.get_mut(var) {
             *v = Some(value);
         } else {
             vars.insert(var.to_owned(), Some(value));
         }
-        self
+    }
+}
+
+/// 定義されている変数を名前順に `{X ↦ 1, Y ↦ 2}` の形で出力します。
+impl<V: fmt::Display> fmt::Display for State<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut vars: Vec<_> = self
+            .0
+            .iter()
+            .filter_map(|(var, value)| value.as_ref().map(|value| (var, value)))
+            .collect();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+
+        write!(f, "{{")?;
+        for (i, (var, value)) in vars.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} ↦ {}", var, value)?;
+        }
+        write!(f, "}}")
     }
 }
 
@@ -159,4 +250,55 @@ pub trait Execute {
         Self: Sized;
 }
 
+pub trait TryEvaluate<T> {
+    type Error;
+
+    /// 与えられた状態のもとで自身を評価します。
+    /// 評価結果と評価後の状態の組を返し、評価できなければエラーを返します。
+    fn try_evaluate(&self, state: State) -> Result<(T, State), Self::Error>;
+}
+
+pub trait TryExecute {
+    type Error;
+
+    /// 与えられた状態のもとで自身を実行します。
+    /// 未実行のコマンドと実行後の状態の組を返し、実行できなければエラーを返します。
+    fn try_execute(&self, state: State) -> Result<(Option<Self>, State), Self::Error>
+    where
+        Self: Sized;
+}
+
+/// 状態を借用して評価する式
+///
+/// 状態を共有参照で受け取るので、評価が状態を変えないことを型で保証します。
+pub trait EvaluateRef<T> {
+    /// 与えられた状態のもとで自身を評価し、評価結果を返します。
+    fn evaluate_ref(&self, state: &State) -> T;
+}
+
+/// 状態を書き換えながら実行するコマンド
+pub trait ExecuteMut {
+    /// 与えられた状態を書き換えながら自身を実行します。
+    fn execute_mut(&self, state: &mut State);
+}
+
+pub trait TryEvaluateRef<T> {
+    type Error;
+
+    /// 与えられた状態のもとで自身を評価し、評価結果を返します。評価できなければエラーを返します。
+    fn try_evaluate_ref(&self, state: &State) -> Result<T, Self::Error>;
+}
+
+pub trait TryExecuteMut {
+    type Error;
+
+    /// 与えられた状態を書き換えながら自身を実行します。実行できなければエラーを返します。
+    /// エラーを返したときの状態は、エラーが起きる直前までの実行を反映しています。
+    fn try_execute_mut(&self, state: &mut State) -> Result<(), Self::Error>;
+}
+
+pub mod arith;
 pub mod imp;
+pub mod integer;
+
+pub use integer::Integer;